@@ -0,0 +1,212 @@
+use crate::backend::chain_of_thought::ChainOfThought;
+use crate::backend::provider::DreamModel;
+use crate::backend::storage::{DreamStore, StoredDream};
+use anyhow::Result;
+use futures::FutureExt;
+use futures::future::LocalBoxFuture;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_util::task::LocalPoolHandle;
+
+pub type Dream = ChainOfThought<DreamModel>;
+
+// Commands waiting for a dream's task before senders start waiting themselves
+const COMMAND_BUFFER: usize = 64;
+
+// Work run by a dream's task, with exclusive access to the dream until its future completes
+type DreamCommand = Box<dyn for<'a> FnOnce(&'a mut Dream) -> LocalBoxFuture<'a, ()> + Send>;
+
+// Each dream lives on its own task and is only changed by the commands sent to it, one at a
+// time and in order. Readers use the snapshot published after every command, so they never
+// wait behind a running LLM call or chain transaction.
+//
+// Agent futures aren't Send, so dream tasks are pinned to the threads of a LocalPoolHandle
+// rather than spawned on the multi-threaded runtime.
+#[derive(Clone)]
+pub struct DreamHandle {
+    commands: mpsc::Sender<DreamCommand>,
+    snapshot: watch::Receiver<StoredDream>,
+    activity: Arc<Activity>,
+}
+
+// What tells an idle dream apart from one in use
+struct Activity {
+    // Commands sent and not finished yet, including ones whose caller went away
+    pending: AtomicUsize,
+    last_used: Mutex<Instant>,
+}
+
+impl Activity {
+    fn touch(&self) {
+        *self
+            .last_used
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_used
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed()
+    }
+}
+
+impl DreamHandle {
+    pub fn spawn(
+        pool: &LocalPoolHandle,
+        dream_id: &str,
+        dream: Dream,
+        store: Arc<dyn DreamStore>,
+    ) -> Self {
+        let (commands, command_rx) = mpsc::channel(COMMAND_BUFFER);
+        let (snapshot_tx, snapshot) = watch::channel(snapshot(dream_id, &dream));
+        let activity = Arc::new(Activity {
+            pending: AtomicUsize::new(0),
+            last_used: Mutex::new(Instant::now()),
+        });
+
+        let dream_id = dream_id.to_string();
+        let task_activity = activity.clone();
+        pool.spawn_pinned(move || {
+            run_dream(
+                dream_id,
+                dream,
+                command_rx,
+                snapshot_tx,
+                store,
+                task_activity,
+            )
+        });
+
+        Self {
+            commands,
+            snapshot,
+            activity,
+        }
+    }
+
+    pub fn touch(&self) {
+        self.activity.touch();
+    }
+
+    // Whether this is the last handle to the dream, no command is queued or running and it
+    // hasn't been used for `idle`. Dropping the last handle ends the dream's task; everything
+    // it did is already in the store, so the next request simply loads it again.
+    pub fn is_idle(&self, idle: Duration) -> bool {
+        self.commands.strong_count() == 1
+            && self.activity.pending.load(Ordering::SeqCst) == 0
+            && self.activity.idle_for() >= idle
+    }
+
+    // The dream as of its last finished command
+    pub fn snapshot(&self) -> StoredDream {
+        self.snapshot.borrow().clone()
+    }
+
+    // Queue `command` behind the ones already sent and wait for its result
+    pub async fn run<R, F>(&self, command: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: for<'a> FnOnce(&'a mut Dream) -> LocalBoxFuture<'a, Result<R>> + Send + 'static,
+    {
+        let (reply_tx, reply) = oneshot::channel();
+        let command: DreamCommand = Box::new(move |dream| {
+            async move {
+                // The caller may have gone away; the dream keeps the result either way
+                let _ = reply_tx.send(command(dream).await);
+            }
+            .boxed_local()
+        });
+
+        self.activity.pending.fetch_add(1, Ordering::SeqCst);
+        if self.commands.send(command).await.is_err() {
+            self.activity.pending.fetch_sub(1, Ordering::SeqCst);
+            return Err(anyhow::anyhow!("Dream task has stopped"));
+        }
+
+        reply
+            .await
+            .map_err(|_| anyhow::anyhow!("Dream task failed while handling the request"))?
+    }
+}
+
+pub fn snapshot(dream_id: &str, dream: &Dream) -> StoredDream {
+    StoredDream {
+        id: dream_id.to_string(),
+        title: dream.dream_title.clone(),
+        theme: dream.dream_theme.clone(),
+        steps: dream.get_steps(),
+        plan: dream.plan.clone(),
+    }
+}
+
+async fn run_dream(
+    dream_id: String,
+    mut dream: Dream,
+    mut commands: mpsc::Receiver<DreamCommand>,
+    snapshot_tx: watch::Sender<StoredDream>,
+    store: Arc<dyn DreamStore>,
+    activity: Arc<Activity>,
+) {
+    while let Some(command) = commands.recv().await {
+        handle(&dream_id, &mut dream, command, &snapshot_tx, &store).await;
+        activity.touch();
+        activity.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Run one command, then persist and publish what it changed
+async fn handle(
+    dream_id: &str,
+    dream: &mut Dream,
+    command: DreamCommand,
+    snapshot_tx: &watch::Sender<StoredDream>,
+    store: &Arc<dyn DreamStore>,
+) {
+    // A panicking command only fails its own request; the dream keeps whatever it had
+    // done so far and goes on with the next one
+    if AssertUnwindSafe(command(dream))
+        .catch_unwind()
+        .await
+        .is_err()
+    {
+        eprintln!("⚠️ A request for dream {} panicked", dream_id);
+    }
+
+    // Only write through real changes, so saving doesn't invalidate the stored SVG
+    let current = snapshot(dream_id, dream);
+    if *snapshot_tx.borrow() == current {
+        return;
+    }
+    if let Err(e) = store.save_dream(&current) {
+        eprintln!("⚠️ Failed to persist dream {}: {}", dream_id, e);
+    }
+    if anchors_broken(&snapshot_tx.borrow(), &current)
+        && let Err(e) = store.mark_nft_stale(dream_id)
+    {
+        eprintln!(
+            "⚠️ Failed to mark the NFT of dream {} stale: {}",
+            dream_id, e
+        );
+    }
+    snapshot_tx.send_replace(current);
+}
+
+// Whether an anchored step went stale or was deleted between `before` and `after`; a minted
+// NFT no longer matches the dream once one has
+fn anchors_broken(before: &StoredDream, after: &StoredDream) -> bool {
+    before
+        .steps
+        .iter()
+        .filter(|step| step.tx_hash.is_some())
+        .any(
+            |step| match after.steps.iter().find(|s| s.step_id == step.step_id) {
+                Some(changed) => changed.stale && !step.stale,
+                None => true,
+            },
+        )
+}
@@ -1,15 +1,45 @@
-use crate::backend::chain_of_thought::{ChainOfThought, ReasoningStep};
+use crate::backend::actor::{self, Dream, DreamHandle};
+use crate::backend::chain::{self, ChainClient};
+use crate::backend::chain_of_thought::{
+    self, ChainOfThought, ChainOfThoughtError, Conclusion, Concurrency, ContextWindow, ModelCall,
+    ReasoningStep, Revision, StepNode,
+};
+use crate::backend::config::Config;
+use crate::backend::continuous::{ContinuousSession, ContinuousSettings, SessionState};
+use crate::backend::events::{DreamEventKind, EventBus};
+use crate::backend::ipfs::{self, KuboClient};
+use crate::backend::pipeline::{self, StepPlan, StepSource, StepSpec};
+use crate::backend::provider::{DreamModel, ModelProvider};
+use crate::backend::schedule;
+use crate::backend::storage::{self, Blob, DreamSchedule, DreamStore, NftRecord, StoredDream};
+use crate::backend::structured;
 use actix_cors::Cors;
-use actix_web::{App, HttpResponse, HttpServer, Responder, web};
-use alith::{Agent, LLM};
+use actix_web::{App, HttpResponse, HttpServer, Responder, http::header, web};
+use alith::Agent;
 use anyhow::Result;
+use chrono::Utc;
+use futures::FutureExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, atomic::AtomicBool};
-use tokio::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use tokio::time::{Duration, sleep};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::LocalPoolHandle;
 use uuid::Uuid;
 
+// How often idle SSE connections get a comment line
+const SSE_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+// Dreams nobody has used for this long are dropped from memory, checked every EVICT_EVERY;
+// they're loaded back from the store on their next request
+const DREAM_IDLE: Duration = Duration::from_secs(10 * 60);
+const EVICT_EVERY: Duration = Duration::from_secs(60);
+
+// Stopped and finished continuous sessions stay queryable for this long
+const SESSION_RETENTION: Duration = Duration::from_secs(10 * 60);
+
 // Request/Response Models
 #[derive(Deserialize)]
 pub struct NewDreamRequest {
@@ -19,7 +49,36 @@ pub struct NewDreamRequest {
 #[derive(Deserialize)]
 pub struct CompleteDreamRequest {
     theme: String,
-    reasoning_steps: Option<Vec<String>>,
+    #[serde(flatten)]
+    steps: StepSource,
+    auto_mint: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct ContinuousDreamRequest {
+    #[serde(flatten)]
+    dream: CompleteDreamRequest,
+    interval_secs: Option<u64>,
+    max_iterations: Option<usize>,
+    evolve_theme: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct MintRequest {
+    recipient: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MintVoucherRequest {
+    wallet: String,
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleRequest {
+    cron: String,
+    theme: String,
+    #[serde(flatten)]
+    steps: StepSource,
     auto_mint: Option<bool>,
 }
 
@@ -32,27 +91,58 @@ pub struct StopDreamingRequest {
 pub struct StepRequest {
     description: String,
     prompt: String,
+    // The steps this one builds on, instead of the steps before it
+    depends_on: Option<Vec<usize>>,
+}
+
+// The process requests take an optional description, which renames the step before it runs
+#[derive(Deserialize)]
+pub struct ProcessStepRequest {
+    description: Option<String>,
+    prompt: String,
+    // Critique-and-revise rounds to run after the first draft (none when not given)
+    reflect: Option<usize>,
+}
+
+// Changes to a step; fields left out stay as they are
+#[derive(Deserialize)]
+pub struct EditStepRequest {
+    description: Option<String>,
+    prompt: Option<String>,
+    depends_on: Option<Vec<usize>>,
+}
+
+#[derive(Deserialize)]
+pub struct BranchRequest {
+    // Alternative continuations to explore (3 when not given)
+    branches: Option<usize>,
+    // What the continuations should explore, if anything in particular
+    focus: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct BooleanStepRequest {
-    description: String,
+    description: Option<String>,
     prompt: String,
+    // Answers to sample and vote over (1 when not given)
+    samples: Option<usize>,
 }
 
 #[derive(Deserialize)]
 pub struct ChoiceStepRequest {
-    description: String,
+    description: Option<String>,
     prompt: String,
     options: Vec<String>,
+    samples: Option<usize>,
 }
 
 #[derive(Deserialize)]
 pub struct NumericStepRequest {
-    description: String,
+    description: Option<String>,
     prompt: String,
     min: i32,
     max: i32,
+    samples: Option<usize>,
 }
 
 #[derive(Serialize)]
@@ -61,6 +151,12 @@ pub struct DreamResponse {
     title: Option<String>,
     theme: Option<String>,
     steps: Vec<ReasoningStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plan: Option<Vec<StepSpec>>,
+    // The steps as a tree, once any step has branched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tree: Option<Vec<StepNode>>,
+    nft: Option<NftRecord>,
 }
 
 #[derive(Serialize)]
@@ -68,7 +164,34 @@ pub struct StepResponse {
     step_id: usize,
     description: String,
     reasoning: String,
-    conclusion: Option<String>,
+    conclusion: Option<Conclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    revisions: Vec<Revision>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    calls: Vec<ModelCall>,
+}
+
+#[derive(Serialize)]
+pub struct ScheduleResponse {
+    #[serde(flatten)]
+    schedule: DreamSchedule,
+    next_run_at: Option<String>,
+}
+
+impl ScheduleResponse {
+    fn new(dream_schedule: DreamSchedule) -> Self {
+        let next_run_at = schedule::parse(&dream_schedule.cron)
+            .ok()
+            .and_then(|cron| schedule::next_run(&cron))
+            .map(schedule::format_time);
+
+        Self {
+            schedule: dream_schedule,
+            next_run_at,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -77,9 +200,12 @@ pub struct SvgResponse {
 }
 
 #[derive(Serialize)]
-pub struct NftResponse {
-    ipfs_cid: String,
-    transaction_hash: String,
+pub struct MintVoucherResponse {
+    token_uri: String,
+    wallet: String,
+    signature: String,
+    signer: String,
+    contract: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -94,151 +220,612 @@ pub struct NftMetadata {
 #[derive(Serialize)]
 pub struct NftAttribute {
     trait_type: String,
-    value: String,
+    value: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_value: Option<i32>,
 }
 
-#[derive(Debug)]
-enum DreamMessage {
-    StartContinuous { dream_id: String, theme: String },
-    StopContinuous { dream_id: String },
+impl NftAttribute {
+    fn text(trait_type: &str, value: impl ToString) -> Self {
+        Self {
+            trait_type: trait_type.to_string(),
+            value: serde_json::Value::String(value.to_string()),
+            display_type: None,
+            max_value: None,
+        }
+    }
+
+    // One attribute per typed step, so marketplaces can filter on what the dream concluded
+    fn from_step(step: &ReasoningStep) -> Option<Self> {
+        let attribute = match step.conclusion.as_ref()? {
+            Conclusion::Open { .. } => return None,
+            Conclusion::Boolean { value } => Self {
+                trait_type: step.desc.clone(),
+                value: serde_json::Value::Bool(*value),
+                display_type: None,
+                max_value: None,
+            },
+            Conclusion::Choice { selected, .. } => Self::text(&step.desc, selected),
+            Conclusion::Numeric { max, value, .. } => Self {
+                trait_type: step.desc.clone(),
+                value: serde_json::Value::from(*value),
+                display_type: Some("number".to_string()),
+                max_value: Some(*max),
+            },
+        };
+        Some(attribute)
+    }
+
+    // How strongly a voted step's samples agreed, so a marketplace shows low-confidence
+    // conclusions for what they are
+    fn agreement(step: &ReasoningStep) -> Option<Self> {
+        let votes = step.votes.as_ref()?;
+        Some(Self {
+            trait_type: format!("{} agreement", step.desc),
+            value: serde_json::Value::from((votes.agreement * 100.0).round() as i64),
+            display_type: Some("boost_percentage".to_string()),
+            max_value: None,
+        })
+    }
 }
 
 pub struct Backend {
-    is_active: Arc<AtomicBool>,
-    dreams: Arc<Mutex<HashMap<String, Arc<Mutex<ChainOfThought<LLM>>>>>>,
-    model_name: String,
-    continuous_dreams: Arc<Mutex<HashMap<String, bool>>>,
-    dream_tx: mpsc::Sender<DreamMessage>,
+    config: Config,
+    dreams: Mutex<HashMap<String, DreamHandle>>,
+    dream_threads: LocalPoolHandle,
+    provider: ModelProvider,
+    store: Arc<dyn DreamStore>,
+    chain: Option<Arc<ChainClient>>,
+    ipfs: Option<KuboClient>,
+    context: Option<ContextWindow>,
+    events: EventBus,
+    continuous_sessions: Mutex<HashMap<String, ContinuousSession>>,
+    // Cancels the task of each running schedule
+    schedules: Mutex<HashMap<String, CancellationToken>>,
 }
 
 impl Backend {
-    pub fn new() -> Result<Self, anyhow::Error> {
-        // Create channel for dream messages
-        let (dream_tx, dream_rx) = mpsc::channel::<DreamMessage>(100);
-
-        let continuous_dreams = Arc::new(Mutex::new(HashMap::new()));
-        let continuous_dreams_clone = continuous_dreams.clone();
-
+    pub fn new(
+        config: Config,
+        provider: ModelProvider,
+        store: Arc<dyn DreamStore>,
+        chain: Option<Arc<ChainClient>>,
+        ipfs: Option<KuboClient>,
+        context: Option<ContextWindow>,
+    ) -> Result<Self, anyhow::Error> {
         let backend = Backend {
-            is_active: Arc::new(AtomicBool::new(true)),
-            dreams: Arc::new(Mutex::new(HashMap::new())),
-            model_name: "gpt-4o-mini".to_string(),
-            continuous_dreams,
-            dream_tx,
+            config,
+            dreams: Mutex::new(HashMap::new()),
+            // One thread per core for the dream tasks; each runs many dreams concurrently
+            dream_threads: LocalPoolHandle::new(
+                std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ),
+            provider,
+            store,
+            chain,
+            ipfs,
+            context,
+            events: EventBus::new(),
+            continuous_sessions: Mutex::new(HashMap::new()),
+            schedules: Mutex::new(HashMap::new()),
         };
 
-        // Start the dream processor in background
-        tokio::spawn(async move {
-            Backend::dream_processor(dream_rx, continuous_dreams_clone).await;
-        });
-
         Ok(backend)
     }
 
-    async fn dream_processor(
-        mut dream_rx: mpsc::Receiver<DreamMessage>,
-        continuous_dreams: Arc<Mutex<HashMap<String, bool>>>,
+    // Build a full dream every `interval` until stopped or `max_iterations` dreams are done,
+    // starting with the already created `first_dream_id`
+    async fn dream_continuously(
+        self: Arc<Self>,
+        session: ContinuousSession,
+        first_dream_id: String,
+        mut settings: ContinuousSettings,
     ) {
-        while let Some(msg) = dream_rx.recv().await {
-            match msg {
-                DreamMessage::StartContinuous { dream_id, theme } => {
-                    // Mark this dream as continuous
-                    {
-                        let mut dreams = continuous_dreams.lock().unwrap();
-                        dreams.insert(dream_id.clone(), true);
+        let mut dream_id = first_dream_id;
+        let mut iterations = 0;
+
+        'session: loop {
+            if let Err(e) = self
+                .complete_dream(&dream_id, settings.steps.clone(), settings.auto_mint)
+                .await
+            {
+                eprintln!("⚠️ Continuous dream {} failed: {:#}", dream_id, e);
+                session.update(|s| s.last_error = Some(format!("Dream {}: {:#}", dream_id, e)));
+            }
+
+            iterations += 1;
+            if settings.max_iterations.is_some_and(|max| iterations >= max) {
+                session.finish();
+                break;
+            }
+
+            // Wait between dreams, then start the next one unless stopped in the meantime
+            dream_id = loop {
+                if !session.wait(settings.interval).await {
+                    break 'session;
+                }
+
+                if settings.evolve_theme {
+                    settings.theme = self.next_theme(&dream_id, settings.theme).await;
+                    session.update(|s| s.theme = settings.theme.clone());
+                }
+                match self.create_dream(&settings.theme).await {
+                    Ok(id) => break id,
+                    Err(e) => {
+                        eprintln!("⚠️ Failed to create continuous dream: {}", e);
+                        session.update(|s| {
+                            s.last_error = Some(format!("Failed to create dream: {}", e))
+                        });
                     }
+                }
+            };
+            session.update(|s| s.dream_ids.push(dream_id.clone()));
+        }
 
-                    // Clone for the task
-                    let continuous_dreams_clone = continuous_dreams.clone();
-
-                    // Process in a separate task
-                    tokio::spawn(async move {
-                        let mut current_dream_id = dream_id;
-
-                        // Continue creating dreams until stopped
-                        loop {
-                            // Check if still active
-                            {
-                                let dreams = continuous_dreams_clone.lock().unwrap();
-                                if !dreams.contains_key(&current_dream_id) {
-                                    break;
-                                }
-                            }
+        println!(
+            "🌙 Continuous dreaming session {} ended after {} dreams",
+            session.status().session_id,
+            iterations
+        );
+    }
+
+    // Theme of the dream after `dream_id`, carried on from its conclusions; `theme` stays
+    // when that fails
+    async fn next_theme(&self, dream_id: &str, theme: String) -> String {
+        let Some(dream) = self.get_dream(dream_id) else {
+            return theme;
+        };
+
+        match dream.run(|cot| cot.next_theme().boxed_local()).await {
+            Ok(next) => next,
+            Err(e) => {
+                eprintln!("⚠️ Failed to evolve theme after dream {}: {}", dream_id, e);
+                theme
+            }
+        }
+    }
 
-                            // Wait between dreams
-                            sleep(Duration::from_secs(30)).await;
+    // Pick up the stored schedules; runs missed while the server was down are skipped
+    fn start_schedules(self: &Arc<Self>) {
+        let schedules = match self.store.list_schedules() {
+            Ok(schedules) => schedules,
+            Err(e) => {
+                eprintln!("⚠️ Failed to load schedules: {}", e);
+                return;
+            }
+        };
 
-                            // Create a new dream ID for next iteration
-                            let next_dream_id = Uuid::new_v4().to_string();
+        for dream_schedule in schedules {
+            match schedule::parse(&dream_schedule.cron) {
+                Ok(cron) => self.spawn_schedule(dream_schedule, cron),
+                Err(e) => eprintln!("⚠️ Skipping schedule {}: {}", dream_schedule.id, e),
+            }
+        }
+    }
 
-                            // Update continuous dreams record
-                            {
-                                let mut dreams = continuous_dreams_clone.lock().unwrap();
-                                dreams.remove(&current_dream_id);
-                                dreams.insert(next_dream_id.clone(), true);
-                            }
+    fn spawn_schedule(self: &Arc<Self>, dream_schedule: DreamSchedule, cron: cron::Schedule) {
+        let cancel = CancellationToken::new();
+        self.schedules_lock()
+            .insert(dream_schedule.id.clone(), cancel.clone());
 
-                            // Update for next iteration
-                            current_dream_id = next_dream_id;
-                        }
-                    });
-                }
-                DreamMessage::StopContinuous { dream_id } => {
-                    let mut dreams = continuous_dreams.lock().unwrap();
-                    dreams.remove(&dream_id);
-                }
+        let runner = self.clone();
+        self.dream_threads
+            .spawn_pinned(move || runner.run_schedule(dream_schedule, cron, cancel));
+    }
+
+    // Run the create-complete pipeline each time `cron` fires, until the schedule is deleted
+    async fn run_schedule(
+        self: Arc<Self>,
+        dream_schedule: DreamSchedule,
+        cron: cron::Schedule,
+        cancel: CancellationToken,
+    ) {
+        let mut after = Utc::now();
+
+        while let Some(next) = cron.after(&after).next() {
+            let delay = (next - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = sleep(delay) => {}
+            }
+
+            println!(
+                "⏰ Running schedule {} ({})",
+                dream_schedule.id, dream_schedule.cron
+            );
+            let (dream_id, error) = match dream_schedule.steps.resolve(&self.config.pipelines) {
+                Ok(steps) => match self.create_dream(&dream_schedule.theme).await {
+                    Ok(dream_id) => {
+                        let error = self
+                            .complete_dream(&dream_id, steps, dream_schedule.auto_mint)
+                            .await
+                            .err();
+                        (Some(dream_id), error)
+                    }
+                    Err(e) => (None, Some(e.context("Failed to create dream"))),
+                },
+                Err(e) => (None, Some(anyhow::anyhow!(e))),
+            };
+
+            let error = error.map(|e| format!("{:#}", e));
+            if let Some(error) = &error {
+                eprintln!("⚠️ Schedule {} failed: {}", dream_schedule.id, error);
+            }
+            if let Err(e) = self.store.record_schedule_run(
+                &dream_schedule.id,
+                dream_id.as_deref(),
+                error.as_deref(),
+            ) {
+                eprintln!(
+                    "⚠️ Failed to record run of schedule {}: {}",
+                    dream_schedule.id, e
+                );
             }
+
+            // A run that overlaps the next firing time skips it rather than catching up
+            after = next.max(Utc::now());
         }
     }
 
-    pub async fn create_dream(&self, theme: &str) -> Result<String, anyhow::Error> {
-        let dream_id = Uuid::new_v4().to_string();
+    fn schedules_lock(&self) -> MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.schedules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn sessions_lock(&self) -> MutexGuard<'_, HashMap<String, ContinuousSession>> {
+        self.continuous_sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
 
-        // Create a new LLM instance for this dream
-        let model = LLM::from_model_name(&self.model_name)?;
+    pub fn get_session(&self, session_id: &str) -> Option<ContinuousSession> {
+        self.sessions_lock().get(session_id).cloned()
+    }
+
+    fn new_agent(&self) -> Result<Agent<DreamModel>, anyhow::Error> {
+        // Create a new model instance for this dream
+        let model = self.provider.build()?;
+
+        Ok(Agent::new("DreamWeaver", model).preamble(&self.config.model.preamble))
+    }
+
+    // Extra agents for processing a dream's independent steps side by side
+    fn concurrency(&self) -> Concurrency<DreamModel> {
+        let provider = self.provider.clone();
+        let preamble = self.config.model.preamble.clone();
+        Concurrency {
+            limit: self.config.model.concurrency,
+            factory: Arc::new(move |index| {
+                let model = provider.build_worker(index)?;
+                Ok(Agent::new("DreamWeaver", model).preamble(&preamble))
+            }),
+        }
+    }
 
-        let agent = Agent::new("DreamWeaver", model)
-            .preamble("You are an artistic AI with exceptional chain of thought reasoning capabilities. You carefully analyze problems step by step and visualize your thinking process as abstract dream-like images.");
+    pub async fn create_dream(&self, theme: &str) -> Result<String, anyhow::Error> {
+        let dream_id = Uuid::new_v4().to_string();
 
-        let mut cot = ChainOfThought::new(agent);
+        let mut cot = ChainOfThought::new(self.new_agent()?);
+        if let Some(context) = self.context {
+            cot.set_context(context);
+        }
+        cot.set_model(self.provider.name());
+        cot.set_concurrency(self.concurrency());
         cot.set_dream_context(theme).await?;
 
-        let cot_arc = Arc::new(Mutex::new(cot));
+        self.store.save_dream(&actor::snapshot(&dream_id, &cot))?;
+
+        let events = self.events.sink(&dream_id);
+        events.emit(DreamEventKind::DreamCreated {
+            title: cot.dream_title.clone(),
+            theme: cot.dream_theme.clone(),
+        });
+        cot.set_events(events);
 
-        let mut dreams = self.dreams.lock().unwrap();
-        dreams.insert(dream_id.clone(), cot_arc);
+        let dream = DreamHandle::spawn(&self.dream_threads, &dream_id, cot, self.store.clone());
+        self.dreams_lock().insert(dream_id.clone(), dream);
 
         Ok(dream_id)
     }
 
-    pub fn get_dream(&self, dream_id: &str) -> Option<Arc<Mutex<ChainOfThought<LLM>>>> {
-        let dreams = self.dreams.lock().unwrap();
-        dreams.get(dream_id).cloned()
+    // The map is only ever held for a lookup or insert, so a poisoned lock still has
+    // consistent contents
+    fn dreams_lock(&self) -> MutexGuard<'_, HashMap<String, DreamHandle>> {
+        self.dreams.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn get_dream(&self, dream_id: &str) -> Option<DreamHandle> {
+        if let Some(dream) = self.dreams_lock().get(dream_id) {
+            dream.touch();
+            return Some(dream.clone());
+        }
+
+        // Not in memory yet, rehydrate it from the store without holding the lock, so other
+        // lookups don't wait on the disk
+        let stored = match self.store.load_dream(dream_id) {
+            Ok(stored) => stored?,
+            Err(e) => {
+                eprintln!("⚠️ Failed to load dream {}: {}", dream_id, e);
+                return None;
+            }
+        };
+        let agent = match self.new_agent() {
+            Ok(agent) => agent,
+            Err(e) => {
+                eprintln!("⚠️ Failed to create agent for dream {}: {}", dream_id, e);
+                return None;
+            }
+        };
+
+        let mut cot = ChainOfThought::restore(agent, stored.title, stored.theme, stored.steps);
+        cot.plan = stored.plan;
+        cot.set_events(self.events.sink(dream_id));
+        if let Some(context) = self.context {
+            cot.set_context(context);
+        }
+        cot.set_model(self.provider.name());
+        cot.set_concurrency(self.concurrency());
+
+        // Another request may have loaded it meanwhile; keep theirs
+        let dream = self
+            .dreams_lock()
+            .entry(dream_id.to_string())
+            .or_insert_with(|| {
+                DreamHandle::spawn(&self.dream_threads, dream_id, cot, self.store.clone())
+            })
+            .clone();
+        dream.touch();
+
+        Some(dream)
+    }
+
+    // Drop idle dreams and long-ended continuous sessions from memory every EVICT_EVERY, so it
+    // doesn't grow with every dream ever touched
+    fn start_eviction(self: &Arc<Self>) {
+        let backend = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(EVICT_EVERY);
+            loop {
+                ticks.tick().await;
+                backend
+                    .dreams_lock()
+                    .retain(|_, dream| !dream.is_idle(DREAM_IDLE));
+                backend
+                    .sessions_lock()
+                    .retain(|_, session| !session.ended_before(SESSION_RETENTION));
+            }
+        });
+    }
+
+    pub fn persist_svg(&self, dream_id: &str, svg: &str) {
+        if let Err(e) = self.store.save_svg(dream_id, svg) {
+            eprintln!("⚠️ Failed to persist SVG for dream {}: {}", dream_id, e);
+        }
+        self.events.sink(dream_id).emit(DreamEventKind::SvgReady {
+            svg_url: format!("/api/dreams/{}/svg", dream_id),
+        });
+    }
+
+    pub fn persist_nft(&self, dream_id: &str, nft: &NftRecord) {
+        if let Err(e) = self.store.save_nft(dream_id, nft) {
+            eprintln!("⚠️ Failed to persist NFT for dream {}: {}", dream_id, e);
+        }
+    }
+
+    // Keep `data` in the local blob store under its CID and pin it on Kubo when configured
+    pub async fn publish(&self, data: Vec<u8>, content_type: &str) -> Result<String> {
+        let cid = ipfs::cid(&data);
+        let blob = Blob {
+            content_type: content_type.to_string(),
+            data,
+        };
+        self.store.save_blob(&cid, &blob)?;
+
+        if let Some(kubo) = &self.ipfs {
+            match kubo.add(blob.data, content_type).await {
+                Ok(pinned) if pinned == cid => {}
+                Ok(pinned) => eprintln!(
+                    "⚠️ IPFS node stored {} under a different CID {}",
+                    cid, pinned
+                ),
+                Err(e) => eprintln!("⚠️ Failed to upload {} to IPFS: {}", cid, e),
+            }
+        }
+
+        Ok(cid)
+    }
+
+    pub fn can_mint(&self) -> bool {
+        self.chain.as_ref().is_some_and(|chain| chain.can_mint())
+    }
+
+    // Take a dream made by `create_dream` the rest of the way: plan its steps if the agent is
+    // to, reason through them, anchor them when a chain is configured, render the SVG and mint it
+    // when asked to and minting is configured
+    pub async fn complete_dream(
+        &self,
+        dream_id: &str,
+        steps: StepPlan,
+        auto_mint: bool,
+    ) -> Result<Option<NftRecord>> {
+        let dream = self
+            .get_dream(dream_id)
+            .ok_or_else(|| anyhow::anyhow!("Dream {} not found", dream_id))?;
+        let chain = self.chain.clone();
+
+        dream
+            .run(move |cot| {
+                async move {
+                    let steps = match steps {
+                        StepPlan::Fixed(steps) => steps,
+                        StepPlan::Planned(count) => cot
+                            .plan_steps(count)
+                            .await
+                            .map_err(|e| e.context("Failed to plan steps"))?,
+                    };
+
+                    let step_ids = pipeline::run(cot, &steps)
+                        .await
+                        .map_err(|e| e.context("Error processing steps"))?;
+
+                    if let Some(chain) = &chain {
+                        for step_id in &step_ids {
+                            if let Err(e) = cot.anchor_to_chain(*step_id, chain).await {
+                                eprintln!("⚠️ Failed to anchor step {}: {}", step_id, e);
+                            }
+                        }
+                    }
+                    Ok(())
+                }
+                .boxed_local()
+            })
+            .await?;
+
+        let svg = dream
+            .run(|cot| cot.generate_svg_dream().boxed_local())
+            .await
+            .map_err(|e| e.context("Failed to generate SVG"))?;
+        self.persist_svg(dream_id, &svg);
+
+        if !(auto_mint && self.can_mint()) {
+            return Ok(None);
+        }
+        match self.mint(dream_id, &dream, None).await {
+            Ok(nft) => Ok(Some(nft)),
+            Err(e) => {
+                eprintln!("⚠️ Failed to mint dream {}: {}", dream_id, e);
+                Ok(None)
+            }
+        }
+    }
+
+    // Content-address the dream's SVG and the metadata pointing at it. Returns both CIDs.
+    async fn publish_dream(
+        &self,
+        dream_id: &str,
+        dream: &DreamHandle,
+    ) -> Result<(String, String), anyhow::Error> {
+        // Reuse the stored SVG while it is still fresh
+        let svg = match self.store.load_svg(dream_id) {
+            Ok(Some(svg)) => svg,
+            _ => {
+                let svg = dream
+                    .run(|cot| cot.generate_svg_dream().boxed_local())
+                    .await?;
+                self.persist_svg(dream_id, &svg);
+                svg
+            }
+        };
+        let ipfs_cid = self.publish(svg.into_bytes(), "image/svg+xml").await?;
+
+        let metadata = self.nft_metadata(
+            &dream.snapshot(),
+            format!("{}/ipfs/{}", self.config.server.base_url, ipfs_cid),
+        );
+        let metadata_cid = self
+            .publish(serde_json::to_vec(&metadata)?, "application/json")
+            .await?;
+
+        Ok((ipfs_cid, metadata_cid))
+    }
+
+    // Content-address the dream's SVG and metadata, mint it on DreamNFT with the metadata's
+    // CID as token URI and record the result
+    pub async fn mint(
+        &self,
+        dream_id: &str,
+        dream: &DreamHandle,
+        recipient: Option<String>,
+    ) -> Result<NftRecord, anyhow::Error> {
+        let chain = self
+            .chain
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Chain is not configured"))?;
+        let (ipfs_cid, metadata_cid) = self.publish_dream(dream_id, dream).await?;
+        let token_uri = self.token_uri(&metadata_cid);
+
+        let receipt = {
+            let (ipfs_cid, token_uri) = (ipfs_cid.clone(), token_uri.clone());
+            dream
+                .run(move |cot| {
+                    async move {
+                        cot.generate_dream_nft(&chain, &ipfs_cid, &token_uri, recipient.as_deref())
+                            .await
+                    }
+                    .boxed_local()
+                })
+                .await?
+        };
+
+        let nft = NftRecord {
+            ipfs_cid,
+            metadata_cid,
+            token_uri,
+            token_id: receipt.token_id,
+            owner: receipt.owner,
+            transaction_hash: receipt.tx_hash,
+            stale: false,
+        };
+        self.persist_nft(dream_id, &nft);
+        self.events
+            .sink(dream_id)
+            .emit(DreamEventKind::NftMinted { nft: nft.clone() });
+
+        Ok(nft)
     }
 
     pub async fn serve(self) -> std::io::Result<()> {
+        let bind = self.config.server.bind.clone();
         let backend = Arc::new(self);
+        backend.start_schedules();
+        backend.start_eviction();
 
         HttpServer::new(move || {
             let backend = backend.clone();
 
             App::new()
-                .wrap(
-                    Cors::default()
-                        .allow_any_origin()
-                        .allow_any_method()
-                        .allow_any_header(),
-                )
+                .wrap(backend.cors())
                 .app_data(web::Data::new(backend.clone()))
                 // Dream management endpoints
                 .route("/api/dreams", web::post().to(create_dream))
                 .route("/api/dreams/{id}", web::get().to(get_dream))
                 // Step management endpoints
                 .route("/api/dreams/{id}/steps", web::post().to(add_step))
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}",
+                    web::patch().to(edit_step),
+                )
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}",
+                    web::delete().to(delete_step),
+                )
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}/rerun",
+                    web::post().to(rerun_step),
+                )
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}/history",
+                    web::get().to(get_step_history),
+                )
                 .route(
                     "/api/dreams/{id}/steps/{step_id}/process",
                     web::post().to(process_step),
                 )
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}/revisions",
+                    web::get().to(get_step_revisions),
+                )
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}/branch",
+                    web::post().to(branch_step),
+                )
+                .route(
+                    "/api/dreams/{id}/steps/{step_id}/choose",
+                    web::post().to(choose_branch),
+                )
                 .route(
                     "/api/dreams/{id}/steps/{step_id}/boolean",
                     web::post().to(process_boolean_step),
@@ -258,12 +845,20 @@ impl Backend {
                 )
                 // SVG and NFT endpoints
                 .route("/api/dreams/{id}/svg", web::get().to(get_svg))
+                // Live progress as Server-Sent Events
+                .route("/api/events", web::get().to(all_events))
+                .route("/api/dreams/{id}/events", web::get().to(dream_events))
                 .route("/api/dreams/{id}/nft", web::post().to(mint_nft))
+                .route(
+                    "/api/dreams/{id}/mint-voucher",
+                    web::post().to(create_mint_voucher),
+                )
                 // One-click and continuous dreaming endpoints
                 .route(
                     "/api/dreams/create-complete",
                     web::post().to(create_complete_dream),
                 )
+                .route("/api/pipelines", web::get().to(list_pipelines))
                 .route(
                     "/api/dreams/continuous/start",
                     web::post().to(start_continuous_dreaming),
@@ -272,60 +867,83 @@ impl Backend {
                     "/api/dreams/continuous/stop",
                     web::get().to(stop_continuous_dreaming),
                 )
+                .route(
+                    "/api/dreams/continuous/{session_id}",
+                    web::get().to(get_continuous_session),
+                )
+                .route(
+                    "/api/dreams/continuous/{session_id}/{action}",
+                    web::post().to(control_continuous_session),
+                )
                 .route("api/metadata/{id}", web::get().to(get_nft_metadata))
+                .route("/ipfs/{cid}", web::get().to(get_ipfs_blob))
+                // Cron schedules that run create-complete
+                .route("/api/schedules", web::post().to(create_schedule))
+                .route("/api/schedules", web::get().to(list_schedules))
+                .route("/api/schedules/{id}", web::delete().to(delete_schedule))
         })
-        .bind("127.0.0.1:8080")?
+        .bind(bind)?
         .run()
         .await
     }
-}
 
-// Helper function to process all steps with appropriate reasoning
-async fn process_all_steps(
-    cot: &mut ChainOfThought<LLM>,
-    step_ids: &[usize],
-) -> Result<(), anyhow::Error> {
-    // Process steps with different reasoning types
-    if step_ids.len() > 0 {
-        cot.process_step(
-            step_ids[0],
-            "What is the current state of Metis L2 technology in terms of scalability, security, and adoption?"
-        ).await?;
-    }
-
-    if step_ids.len() > 1 {
-        cot.process_boolean_step(
-            step_ids[1],
-            "Is AI enhancement essential for the future growth of Metis L2 solutions?",
-        )
-        .await?;
-    }
-
-    if step_ids.len() > 2 {
-        cot.process_string_choice_step(
-            step_ids[2],
-            "Which area of integration offers the most immediate value for Metis L2?",
-            &[
-                "Transaction optimization",
-                "Smart contract enhancement",
-                "Oracle improvements",
-                "User experience",
-            ],
-        )
-        .await?;
+    fn cors(&self) -> Cors {
+        let origins = &self.config.server.cors_origins;
+        let cors = if origins.iter().any(|origin| origin == "*") {
+            Cors::default().allow_any_origin()
+        } else {
+            origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        };
+
+        cors.allow_any_method().allow_any_header()
     }
 
-    if step_ids.len() > 3 {
-        cot.process_numeric_step(
-            step_ids[3],
-            "In how many years will AI-enhanced blockchain solutions become mainstream on Metis?",
-            1,
-            10,
-        )
-        .await?;
+    // Token URI a dream is minted with, whether by the backend or through a voucher. It names
+    // the metadata by CID so it can't change after minting: an ipfs:// URI once the metadata
+    // is pinned on an IPFS node, otherwise this server's /ipfs gateway.
+    fn token_uri(&self, metadata_cid: &str) -> String {
+        match &self.ipfs {
+            Some(_) => format!("ipfs://{}", metadata_cid),
+            None => format!("{}/ipfs/{}", self.config.server.base_url, metadata_cid),
+        }
     }
 
-    Ok(())
+    fn nft_metadata(&self, dream: &StoredDream, image: String) -> NftMetadata {
+        let steps = &dream.steps;
+
+        NftMetadata {
+            name: dream
+                .title
+                .clone()
+                .unwrap_or(format!("Dream #{}", dream.id)),
+            description: format!(
+                "AI Dreamcatcher visualization: {}",
+                dream
+                    .theme
+                    .clone()
+                    .unwrap_or_else(|| "Ethereal Dream".to_string())
+            ),
+            image,
+            external_url: Some(format!(
+                "{}/dreams/{}",
+                self.config.server.base_url, dream.id
+            )),
+            attributes: [
+                NftAttribute::text("Theme", dream.theme.as_deref().unwrap_or("Unknown")),
+                NftAttribute::text("Steps Count", steps.len()),
+                NftAttribute::text("Anchored", steps.iter().any(|s| s.tx_hash.is_some())),
+            ]
+            .into_iter()
+            .chain(steps.iter().flat_map(|step| {
+                NftAttribute::from_step(step)
+                    .into_iter()
+                    .chain(NftAttribute::agreement(step))
+            }))
+            .collect(),
+        }
+    }
 }
 
 // API Handler functions
@@ -336,23 +954,25 @@ async fn create_dream(
 ) -> impl Responder {
     match backend.create_dream(&req.theme).await {
         Ok(dream_id) => HttpResponse::Created().json(serde_json::json!({ "id": dream_id })),
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({ "error": e.to_string() }))
-        }
+        Err(e) => step_error(e),
     }
 }
 
 async fn get_dream(backend: web::Data<Arc<Backend>>, path: web::Path<String>) -> impl Responder {
     let dream_id = path.into_inner();
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let cot = cot_arc.lock().unwrap();
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        let dream = dream.snapshot();
+        let nft = backend.store.load_nft(&dream_id).ok().flatten();
 
         let response = DreamResponse {
             id: dream_id,
-            title: cot.dream_title.clone(),
-            theme: cot.dream_theme.clone(),
-            steps: cot.get_steps(),
+            title: dream.title,
+            theme: dream.theme,
+            tree: chain_of_thought::step_tree(&dream.steps),
+            steps: dream.steps,
+            plan: dream.plan,
+            nft,
         };
 
         HttpResponse::Ok().json(response)
@@ -368,94 +988,440 @@ async fn add_step(
 ) -> impl Responder {
     let dream_id = path.into_inner();
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
-
-        let step_id = cot.add_step(req.description.clone());
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        let StepRequest {
+            description,
+            prompt,
+            depends_on,
+        } = req.into_inner();
+
+        let steps = dream.snapshot().steps;
+        if let Some(unknown) = depends_on
+            .iter()
+            .flatten()
+            .find(|id| !steps.iter().any(|s| s.step_id == **id))
+        {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("depends_on names unknown step {}", unknown)
+            }));
+        }
 
-        HttpResponse::Created().json(serde_json::json!({ "step_id": step_id }))
+        match dream
+            .run(move |cot| {
+                async move {
+                    let step_id = cot.add_prompted_step(description, prompt);
+                    if depends_on.is_some() {
+                        cot.set_depends_on(step_id, depends_on)?;
+                    }
+                    Ok(step_id)
+                }
+                .boxed_local()
+            })
+            .await
+        {
+            Ok(step_id) => HttpResponse::Created().json(serde_json::json!({ "step_id": step_id })),
+            Err(e) => step_error(e),
+        }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
     }
 }
 
+// Rename a step to the description of a process request, when one is given and differs
+fn rename_step(
+    cot: &mut Dream,
+    step_id: usize,
+    description: Option<String>,
+) -> Result<(), anyhow::Error> {
+    let Some(description) = description.filter(|d| !d.trim().is_empty()) else {
+        return Ok(());
+    };
+    if cot.get_step(step_id).is_some_and(|s| s.desc != description) {
+        cot.edit_step(step_id, Some(description), None)?;
+    }
+    Ok(())
+}
+
+// Missing, finished and misused steps are the request's fault, and answers that never parsed
+// or failed calls are the model's or the chain's, not the server's
+fn step_error(e: anyhow::Error) -> HttpResponse {
+    let Some(error) = e.downcast_ref::<ChainOfThoughtError>() else {
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("{:#}", e) }));
+    };
+    let body = serde_json::json!({ "error": error.to_string() });
+
+    match error {
+        ChainOfThoughtError::StepNotFound { .. } => HttpResponse::NotFound().json(body),
+        ChainOfThoughtError::InvalidDependency { .. } | ChainOfThoughtError::NotABranch { .. } => {
+            HttpResponse::BadRequest().json(body)
+        }
+        ChainOfThoughtError::AlreadyProcessed { .. }
+        | ChainOfThoughtError::NotProcessed { .. }
+        | ChainOfThoughtError::AlreadyAnchored { .. }
+        | ChainOfThoughtError::StepInUse { .. } => HttpResponse::Conflict().json(body),
+        ChainOfThoughtError::ParseFailed(parse_error) => {
+            HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                "error": parse_error.to_string(),
+                "step_id": parse_error.step_id,
+                "attempts": parse_error.attempts,
+                "reason": parse_error.reason,
+                "response": parse_error.response,
+            }))
+        }
+        ChainOfThoughtError::LlmFailed { .. }
+        | ChainOfThoughtError::Anchoring { .. }
+        | ChainOfThoughtError::Minting { .. } => HttpResponse::BadGateway().json(body),
+    }
+}
+
 async fn process_step(
     backend: web::Data<Arc<Backend>>,
     path: web::Path<(String, usize)>,
-    req: web::Json<StepRequest>,
+    req: web::Json<ProcessStepRequest>,
 ) -> impl Responder {
     let (dream_id, step_id) = path.into_inner();
+    let ProcessStepRequest {
+        description,
+        prompt,
+        reflect,
+    } = req.into_inner();
+    let reflect = match chain_of_thought::reflect_rounds(reflect) {
+        Ok(reflect) => reflect,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
-
-        match cot.process_step(step_id, &req.prompt).await {
-            Ok(reasoning) => {
-                let steps = cot.get_steps();
-                let step = steps.iter().find(|s| s.step_id == step_id);
-
-                if let Some(step) = step {
-                    let response = StepResponse {
-                        step_id,
-                        description: step.desc.clone(),
-                        reasoning: step.reasoning.clone(),
-                        conclusion: step.conclusion.clone(),
-                    };
-
-                    HttpResponse::Ok().json(response)
-                } else {
-                    HttpResponse::InternalServerError()
-                        .json(serde_json::json!({ "error": "Step not found after processing" }))
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        let processed = dream
+            .run(move |cot| {
+                async move {
+                    cot.ensure_unprocessed(step_id)?;
+                    rename_step(cot, step_id, description)?;
+                    cot.process_step(step_id, &prompt, reflect).await?;
+                    Ok(cot.get_step(step_id))
                 }
+                .boxed_local()
+            })
+            .await;
+
+        match processed {
+            Ok(Some(step)) => {
+                let response = StepResponse {
+                    step_id,
+                    description: step.desc,
+                    reasoning: step.reasoning,
+                    conclusion: step.conclusion,
+                    summary: step.summary,
+                    revisions: step.revisions,
+                    calls: step.calls,
+                };
+
+                HttpResponse::Ok().json(response)
             }
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
+            Ok(None) => HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": "Step not found after processing" })),
+            Err(e) => step_error(e),
         }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
     }
 }
 
-async fn process_boolean_step(
+// The drafts and critiques of a step processed with reflection, oldest first
+async fn get_step_revisions(
     backend: web::Data<Arc<Backend>>,
     path: web::Path<(String, usize)>,
-    req: web::Json<BooleanStepRequest>,
 ) -> impl Responder {
     let (dream_id, step_id) = path.into_inner();
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
 
-        match cot.process_boolean_step(step_id, &req.prompt).await {
-            Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "result": result })),
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
-        }
-    } else {
-        HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
+    match dream
+        .snapshot()
+        .steps
+        .into_iter()
+        .find(|s| s.step_id == step_id)
+    {
+        Some(step) => HttpResponse::Ok().json(serde_json::json!({
+            "step_id": step_id,
+            "description": step.desc,
+            "revisions": step.revisions,
+        })),
+        None => HttpResponse::NotFound().json(serde_json::json!({ "error": "Step not found" })),
     }
 }
 
-async fn process_choice_step(
+// Change a step's description, prompt or dependencies. Its reasoning stays until it's rerun.
+async fn edit_step(
     backend: web::Data<Arc<Backend>>,
     path: web::Path<(String, usize)>,
-    req: web::Json<ChoiceStepRequest>,
+    req: web::Json<EditStepRequest>,
 ) -> impl Responder {
     let (dream_id, step_id) = path.into_inner();
+    let EditStepRequest {
+        description,
+        prompt,
+        depends_on,
+    } = req.into_inner();
+
+    if description.is_none() && prompt.is_none() && depends_on.is_none() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Give at least one of description, prompt or depends_on"
+        }));
+    }
+    if let Some(field) = [("description", &description), ("prompt", &prompt)]
+        .into_iter()
+        .find_map(|(field, value)| {
+            value
+                .as_ref()
+                .is_some_and(|v| v.trim().is_empty())
+                .then_some(field)
+        })
+    {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({ "error": format!("{} must not be empty", field) }));
+    }
+
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
+
+    let edited = dream
+        .run(move |cot| {
+            async move {
+                if depends_on.is_some() {
+                    cot.set_depends_on(step_id, depends_on)?;
+                }
+                if description.is_none() && prompt.is_none() {
+                    return cot
+                        .get_step(step_id)
+                        .ok_or(ChainOfThoughtError::StepNotFound { step_id }.into());
+                }
+                cot.edit_step(step_id, description, prompt)
+            }
+            .boxed_local()
+        })
+        .await;
+
+    match edited {
+        Ok(step) => HttpResponse::Ok().json(step),
+        Err(e) => step_error(e),
+    }
+}
+
+async fn delete_step(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
+
+    match dream
+        .run(move |cot| async move { cot.delete_step(step_id) }.boxed_local())
+        .await
+    {
+        Ok(step) => HttpResponse::Ok().json(serde_json::json!({ "deleted": step })),
+        Err(e) => step_error(e),
+    }
+}
+
+// Run a step again the way it was last run, keeping the earlier runs in its history
+async fn rerun_step(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
+
+    // A step that was only added has nothing to rerun yet
+    let rerun = dream
+        .run(move |cot| {
+            async move {
+                cot.rerun_step(step_id).await?;
+                cot.get_step(step_id)
+                    .ok_or_else(|| anyhow::anyhow!("Step not found"))
+            }
+            .boxed_local()
+        })
+        .await;
+
+    match rerun {
+        Ok(step) => HttpResponse::Ok().json(step),
+        Err(e) => step_error(e),
+    }
+}
+
+async fn get_step_history(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
+
+    match dream
+        .snapshot()
+        .steps
+        .into_iter()
+        .find(|s| s.step_id == step_id)
+    {
+        Some(step) => HttpResponse::Ok().json(serde_json::json!({
+            "step_id": step_id,
+            "description": step.desc,
+            "stale": step.stale,
+            "history": step.history,
+        })),
+        None => HttpResponse::NotFound().json(serde_json::json!({ "error": "Step not found" })),
+    }
+}
+
+// Fork a concluded step into alternative continuations, explore and score them, and choose one
+async fn branch_step(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+    req: web::Json<BranchRequest>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+    let BranchRequest { branches, focus } = req.into_inner();
+    let count = match chain_of_thought::branch_count(branches) {
+        Ok(count) => count,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
+
+    // A step that hasn't concluded has nothing to branch from
+    let branched = dream
+        .run(move |cot| {
+            async move {
+                let branch_ids = cot.branch_step(step_id, count, focus.as_deref()).await?;
+                Ok(branch_ids
+                    .into_iter()
+                    .filter_map(|id| cot.get_step(id))
+                    .collect::<Vec<_>>())
+            }
+            .boxed_local()
+        })
+        .await;
+
+    match branched {
+        Ok(branches) => HttpResponse::Ok().json(serde_json::json!({
+            "step_id": step_id,
+            "chosen": branches
+                .iter()
+                .find(|s| s.chosen == Some(true))
+                .map(|s| s.step_id),
+            "branches": branches,
+        })),
+        Err(e) => step_error(e),
+    }
+}
+
+// Take a branch as the path over the one its scores picked
+async fn choose_branch(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    };
+
+    match dream
+        .run(move |cot| async move { cot.choose_branch(step_id) }.boxed_local())
+        .await
+    {
+        Ok(parent) => HttpResponse::Ok().json(serde_json::json!({
+            "parent": parent,
+            "chosen": step_id,
+        })),
+        Err(e) => step_error(e),
+    }
+}
+
+async fn process_boolean_step(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+    req: web::Json<BooleanStepRequest>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+    let BooleanStepRequest {
+        description,
+        prompt,
+        samples,
+    } = req.into_inner();
+    let samples = match structured::sample_count(samples) {
+        Ok(samples) => samples,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        match dream
+            .run(move |cot| {
+                async move {
+                    cot.ensure_unprocessed(step_id)?;
+                    rename_step(cot, step_id, description)?;
+                    cot.process_boolean_step(step_id, &prompt, samples).await
+                }
+                .boxed_local()
+            })
+            .await
+        {
+            Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "result": result })),
+            Err(e) => step_error(e),
+        }
+    } else {
+        HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
+    }
+}
+
+async fn process_choice_step(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, usize)>,
+    req: web::Json<ChoiceStepRequest>,
+) -> impl Responder {
+    let (dream_id, step_id) = path.into_inner();
+    let ChoiceStepRequest {
+        description,
+        prompt,
+        options,
+        samples,
+    } = req.into_inner();
+    let samples = match structured::sample_count(samples) {
+        Ok(samples) => samples,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
-
-        // Convert Vec<String> to Vec<&str> for the function call
-        let options: Vec<&str> = req.options.iter().map(AsRef::as_ref).collect();
-
-        match cot
-            .process_string_choice_step(step_id, &req.prompt, &options)
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        match dream
+            .run(move |cot| {
+                async move {
+                    // Convert Vec<String> to Vec<&str> for the function call
+                    let options: Vec<&str> = options.iter().map(AsRef::as_ref).collect();
+                    cot.ensure_unprocessed(step_id)?;
+                    rename_step(cot, step_id, description)?;
+                    cot.process_string_choice_step(step_id, &prompt, &options, samples)
+                        .await
+                }
+                .boxed_local()
+            })
             .await
         {
             Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "result": result })),
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
+            Err(e) => step_error(e),
         }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
@@ -468,17 +1434,33 @@ async fn process_numeric_step(
     req: web::Json<NumericStepRequest>,
 ) -> impl Responder {
     let (dream_id, step_id) = path.into_inner();
+    let NumericStepRequest {
+        description,
+        prompt,
+        min,
+        max,
+        samples,
+    } = req.into_inner();
+    let samples = match structured::sample_count(samples) {
+        Ok(samples) => samples,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
-
-        match cot
-            .process_numeric_step(step_id, &req.prompt, req.min, req.max)
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        match dream
+            .run(move |cot| {
+                async move {
+                    cot.ensure_unprocessed(step_id)?;
+                    rename_step(cot, step_id, description)?;
+                    cot.process_numeric_step(step_id, &prompt, min, max, samples)
+                        .await
+                }
+                .boxed_local()
+            })
             .await
         {
             Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "result": result })),
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
+            Err(e) => step_error(e),
         }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
@@ -491,13 +1473,30 @@ async fn anchor_step(
 ) -> impl Responder {
     let (dream_id, step_id) = path.into_inner();
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
+    let Some(chain) = backend.chain.clone() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Chain anchoring is not configured (set CHAIN_RPC_URL and CHAIN_PRIVATE_KEY)"
+        }));
+    };
 
-        match cot.anchor_to_chain(step_id).await {
-            Ok(tx_hash) => HttpResponse::Ok().json(serde_json::json!({ "tx_hash": tx_hash })),
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        let anchored = dream
+            .run(move |cot| {
+                async move {
+                    let tx_hash = cot.anchor_to_chain(step_id, &chain).await?;
+                    Ok((tx_hash, cot.get_step(step_id)))
+                }
+                .boxed_local()
+            })
+            .await;
+
+        match anchored {
+            Ok((tx_hash, step)) => HttpResponse::Ok().json(serde_json::json!({
+                "tx_hash": tx_hash,
+                "block_number": step.as_ref().and_then(|s| s.block_number),
+                "digest": step.and_then(|s| s.anchor_digest),
+            })),
+            Err(e) => step_error(e),
         }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
@@ -507,246 +1506,423 @@ async fn anchor_step(
 async fn get_svg(backend: web::Data<Arc<Backend>>, path: web::Path<String>) -> impl Responder {
     let dream_id = path.into_inner();
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        // Serve the stored rendering while it is still current
+        if let Ok(Some(svg)) = backend.store.load_svg(&dream_id) {
+            return HttpResponse::Ok().content_type("image/svg+xml").body(svg);
+        }
 
-        match cot.generate_svg_dream().await {
-            Ok(svg) => HttpResponse::Ok().content_type("image/svg+xml").body(svg),
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
+        match dream
+            .run(|cot| cot.generate_svg_dream().boxed_local())
+            .await
+        {
+            Ok(svg) => {
+                backend.persist_svg(&dream_id, &svg);
+                HttpResponse::Ok().content_type("image/svg+xml").body(svg)
+            }
+            Err(e) => step_error(e),
         }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
     }
 }
 
-async fn mint_nft(backend: web::Data<Arc<Backend>>, path: web::Path<String>) -> impl Responder {
+async fn mint_nft(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<String>,
+    req: Option<web::Json<MintRequest>>,
+) -> impl Responder {
     let dream_id = path.into_inner();
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
+    if !backend.can_mint() {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "NFT minting is not configured (set CHAIN_RPC_URL, CHAIN_PRIVATE_KEY and DREAM_NFT_CONTRACT_ADDRESS)"
+        }));
+    }
+    let recipient = req.and_then(|req| req.into_inner().recipient);
+    // A bad address is the request's fault, so catch it before it can look like a failed mint
+    if let Some(Err(e)) = recipient
+        .as_deref()
+        .map(|address| chain::parse_address(address, "recipient"))
+    {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() }));
+    }
 
-        match cot.generate_dream_nft().await {
-            Ok((ipfs_cid, tx_hash)) => HttpResponse::Ok().json(NftResponse {
-                ipfs_cid,
-                transaction_hash: tx_hash,
-            }),
-            Err(e) => HttpResponse::InternalServerError()
-                .json(serde_json::json!({ "error": e.to_string() })),
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        match backend.mint(&dream_id, &dream, recipient).await {
+            Ok(nft) => HttpResponse::Ok().json(nft),
+            Err(e) => step_error(e),
         }
     } else {
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
     }
 }
 
-// New endpoints for user-friendly interaction
+// Stream bus events as SSE frames, optionally only those of one dream, with periodic
+// comments so proxies keep idle connections open
+fn event_stream(backend: &Backend, dream_id: Option<String>) -> HttpResponse {
+    let events = BroadcastStream::new(backend.events.subscribe()).filter_map(move |event| {
+        // Lagged subscribers just skip the events they missed
+        let event = event.ok()?;
+        if dream_id.as_ref().is_some_and(|id| *id != event.dream_id) {
+            return None;
+        }
+        Some(Ok::<_, actix_web::Error>(web::Bytes::from(event.to_sse())))
+    });
+    let keep_alive = IntervalStream::new(tokio::time::interval(SSE_KEEP_ALIVE))
+        .map(|_| Ok(web::Bytes::from_static(b": keep-alive\n\n")));
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events.merge(keep_alive))
+}
 
-async fn create_complete_dream(
+// Events for every dream, so clients can follow dreams whose ID they don't know yet
+// (e.g. the one being built by create-complete)
+async fn all_events(backend: web::Data<Arc<Backend>>) -> impl Responder {
+    event_stream(&backend, None)
+}
+
+async fn dream_events(backend: web::Data<Arc<Backend>>, path: web::Path<String>) -> impl Responder {
+    let dream_id = path.into_inner();
+
+    if backend.get_dream(&dream_id).is_none() {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }));
+    }
+
+    event_stream(&backend, Some(dream_id))
+}
+
+// Sign a voucher so the user's own wallet can mint the dream and pay the gas
+async fn create_mint_voucher(
     backend: web::Data<Arc<Backend>>,
-    req: web::Json<CompleteDreamRequest>,
+    path: web::Path<String>,
+    req: web::Json<MintVoucherRequest>,
 ) -> impl Responder {
-    // 1. Create a new dream
-    let dream_id = match backend.create_dream(&req.theme).await {
-        Ok(id) => id,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create dream: {}", e)
-            }));
-        }
+    let dream_id = path.into_inner();
+
+    let Some(chain) = backend.chain.clone() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Mint vouchers need the DreamNFT owner key (set CHAIN_RPC_URL and CHAIN_PRIVATE_KEY)"
+        }));
     };
+    // Before publishing, which may render the SVG and pin it
+    if let Err(e) = chain::parse_address(&req.wallet, "wallet") {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() }));
+    }
 
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
+    let Some(dream) = backend.get_dream(&dream_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Dream not found"
+        }));
+    };
 
-        // 2. Define reasoning steps (default or custom)
-        let reasoning_steps = req.reasoning_steps.clone().unwrap_or_else(|| {
-            vec![
-                "Current state analysis".to_string(),
-                "Future possibilities".to_string(),
-                "Integration potential".to_string(),
-                "Vision synthesis".to_string(),
-            ]
-        });
+    let token_uri = match backend.publish_dream(&dream_id, &dream).await {
+        Ok((_, metadata_cid)) => backend.token_uri(&metadata_cid),
+        Err(e) => return step_error(e.context("Failed to publish dream")),
+    };
 
-        let mut step_ids = Vec::new();
-        for step_desc in reasoning_steps {
-            step_ids.push(cot.add_step(step_desc));
-        }
+    match chain.sign_mint_voucher(&req.wallet, &token_uri) {
+        Ok(voucher) => HttpResponse::Ok().json(MintVoucherResponse {
+            token_uri,
+            wallet: req.wallet.clone(),
+            signature: voucher.signature,
+            signer: voucher.signer,
+            contract: chain.nft_contract(),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to sign voucher: {}", e)
+        })),
+    }
+}
 
-        // 3. Process all steps with appropriate reasoning types
-        if let Err(e) = process_all_steps(&mut cot, &step_ids).await {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Error processing steps: {}", e)
-            }));
-        }
+// New endpoints for user-friendly interaction
 
-        // 4. Anchor all steps to blockchain
-        for step_id in &step_ids {
-            let _ = cot.anchor_to_chain(*step_id).await; // Ignore errors for demo
+async fn create_complete_dream(
+    backend: web::Data<Arc<Backend>>,
+    req: web::Json<CompleteDreamRequest>,
+) -> impl Responder {
+    // 1. Work out the reasoning steps (planned by the agent unless the request picks them)
+    let steps = match req.steps.resolve(&backend.config.pipelines) {
+        Ok(steps) => steps,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": e }));
         }
+    };
 
-        // 5. Generate SVG visualization
-        let svg = match cot.generate_svg_dream().await {
-            Ok(svg) => svg,
-            Err(e) => {
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to generate SVG: {}", e)
-                }));
-            }
-        };
+    // 2. Create a new dream
+    let dream_id = match backend.create_dream(&req.theme).await {
+        Ok(id) => id,
+        Err(e) => return step_error(e),
+    };
 
-        // 6. Mint NFT if requested (default to true)
-        let nft_result = if req.auto_mint.unwrap_or(true) {
-            match cot.generate_dream_nft().await {
-                Ok((ipfs_cid, tx_hash)) => Some((ipfs_cid, tx_hash)),
-                Err(_) => None,
-            }
-        } else {
-            None
-        };
+    // 3. Process, anchor, render and mint (default to true)
+    let nft_result = match backend
+        .complete_dream(&dream_id, steps, req.auto_mint.unwrap_or(true))
+        .await
+    {
+        Ok(nft) => nft,
+        Err(e) => return step_error(e),
+    };
 
-        // 7. Return complete result
+    // 4. Return complete result
+    if let Some(dream) = backend.get_dream(&dream_id) {
+        let dream = dream.snapshot();
         return HttpResponse::Ok().json(serde_json::json!({
             "dream_id": dream_id,
-            "title": cot.dream_title,
-            "theme": cot.dream_theme,
-            "steps": cot.get_steps(),
+            "title": dream.title,
+            "theme": dream.theme,
+            "tree": chain_of_thought::step_tree(&dream.steps),
+            "steps": dream.steps,
+            "plan": dream.plan,
             "svg_url": format!("/api/dreams/{}/svg", dream_id),
-            "nft": nft_result.map(|(cid, tx)| {
-                serde_json::json!({
-                    "ipfs_cid": cid,
-                    "transaction_hash": tx
-                })
-            }),
+            "nft": nft_result,
         }));
     }
 
     HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream processing failed" }))
 }
 
+// Named pipelines that create-complete, continuous and scheduled dreams can pick by name
+async fn list_pipelines(backend: web::Data<Arc<Backend>>) -> impl Responder {
+    HttpResponse::Ok().json(&backend.config.pipelines)
+}
+
 async fn start_continuous_dreaming(
     backend: web::Data<Arc<Backend>>,
-    req: web::Json<CompleteDreamRequest>,
+    req: web::Json<ContinuousDreamRequest>,
 ) -> impl Responder {
-    // Create initial dream
-    let dream_id = match backend.create_dream(&req.theme).await {
-        Ok(id) => id,
+    let ContinuousDreamRequest {
+        dream,
+        interval_secs,
+        max_iterations,
+        evolve_theme,
+    } = req.into_inner();
+
+    if interval_secs == Some(0) || max_iterations == Some(0) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "interval_secs and max_iterations must be at least 1"
+        }));
+    }
+
+    let steps = match dream.steps.resolve(&backend.config.pipelines) {
+        Ok(steps) => steps,
         Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create dream: {}", e)
-            }));
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": e }));
         }
     };
 
-    // Process the first dream completely
-    if let Some(cot_arc) = backend.get_dream(&dream_id) {
-        let mut cot = cot_arc.lock().unwrap();
+    let defaults = &backend.config.continuous;
+    let settings = ContinuousSettings {
+        theme: dream.theme,
+        steps,
+        auto_mint: dream.auto_mint.unwrap_or(true),
+        interval: Duration::from_secs(interval_secs.unwrap_or(defaults.interval_secs)),
+        max_iterations: max_iterations.or(defaults.max_iterations),
+        evolve_theme: evolve_theme.unwrap_or(defaults.evolve_theme),
+    };
 
-        // Add default steps
-        let mut step_ids = Vec::new();
-        for step_desc in ["Analysis", "Possibilities", "Integration", "Vision"].iter() {
-            step_ids.push(cot.add_step(step_desc.to_string()));
-        }
+    // Create initial dream; the session builds it and every dream after it in the background
+    let dream_id = match backend.create_dream(&settings.theme).await {
+        Ok(id) => id,
+        Err(e) => return step_error(e),
+    };
 
-        // Process all steps
-        let _ = process_all_steps(&mut cot, &step_ids).await;
+    let session_id = Uuid::new_v4().to_string();
+    let session = ContinuousSession::new(&session_id, &dream_id, &settings);
+    backend
+        .sessions_lock()
+        .insert(session_id.clone(), session.clone());
 
-        // Anchor steps to blockchain
-        for step_id in &step_ids {
-            let _ = cot.anchor_to_chain(*step_id).await;
-        }
+    let status = session.status();
+    let runner = backend.get_ref().clone();
+    let first_dream_id = dream_id.clone();
+    backend
+        .dream_threads
+        .spawn_pinned(move || runner.dream_continuously(session, first_dream_id, settings));
 
-        // Generate SVG and mint NFT
-        if let Ok(svg) = cot.generate_svg_dream().await {
-            let _ = cot.generate_dream_nft().await;
-        }
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "Continuous dreaming started",
+        "session_id": session_id,
+        "initial_dream_id": dream_id,
+        "interval_secs": status.interval_secs,
+        "max_iterations": status.max_iterations,
+        "status_url": format!("/api/dreams/continuous/{}", session_id),
+        "control_url": format!("/api/dreams/continuous/{}/stop", session_id)
+    }))
+}
+
+async fn get_continuous_session(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match backend.get_session(&path.into_inner()) {
+        Some(session) => HttpResponse::Ok().json(session.status()),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Continuous dreaming session not found"
+        })),
     }
+}
 
-    // Send message to start continuous dreaming
-    let theme = req.theme.clone();
-    let dream_id_clone = dream_id.clone();
+async fn control_continuous_session(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (session_id, action) = path.into_inner();
+
+    let state = match action.as_str() {
+        "pause" => SessionState::Paused,
+        "resume" => SessionState::Running,
+        "stop" => SessionState::Stopped,
+        _ => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": format!("Unknown action '{}', expected pause, resume or stop", action)
+            }));
+        }
+    };
 
-    if let Err(_) = backend
-        .dream_tx
-        .send(DreamMessage::StartContinuous {
-            dream_id: dream_id_clone,
-            theme,
-        })
-        .await
-    {
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Failed to start continuous dreaming"
+    let Some(session) = backend.get_session(&session_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Continuous dreaming session not found"
         }));
-    }
+    };
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "Continuous dreaming started",
-        "initial_dream_id": dream_id,
-        "control_url": format!("/api/dreams/continuous/stop?id={}", dream_id)
-    }))
+    match session.request(state) {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::Conflict().json(serde_json::json!({ "error": e })),
+    }
 }
 
+// Older clients stop a session by the ID of any of its dreams
 async fn stop_continuous_dreaming(
     backend: web::Data<Arc<Backend>>,
     query: web::Query<StopDreamingRequest>,
 ) -> impl Responder {
-    if let Err(_) = backend
-        .dream_tx
-        .send(DreamMessage::StopContinuous {
-            dream_id: query.id.clone(),
-        })
-        .await
-    {
+    let session = backend.get_session(&query.id).or_else(|| {
+        backend
+            .sessions_lock()
+            .values()
+            .find(|session| session.has_dream(&query.id))
+            .cloned()
+    });
+    let Some(session) = session else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Continuous dreaming session not found"
+        }));
+    };
+
+    match session.request(SessionState::Stopped) {
+        Ok(status) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "Continuous dreaming stopped",
+            "session_id": status.session_id,
+            "dream_id": query.id
+        })),
+        Err(e) => HttpResponse::Conflict().json(serde_json::json!({ "error": e })),
+    }
+}
+
+async fn create_schedule(
+    backend: web::Data<Arc<Backend>>,
+    req: web::Json<ScheduleRequest>,
+) -> impl Responder {
+    let req = req.into_inner();
+
+    let cron = match schedule::parse(&req.cron) {
+        Ok(cron) => cron,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() }));
+        }
+    };
+    if let Err(e) = req.steps.resolve(&backend.config.pipelines) {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": e }));
+    }
+
+    let dream_schedule = DreamSchedule {
+        id: Uuid::new_v4().to_string(),
+        cron: req.cron.trim().to_string(),
+        theme: req.theme,
+        steps: req.steps,
+        auto_mint: req.auto_mint.unwrap_or(true),
+        created_at: storage::now(),
+        last_run_at: None,
+        last_dream_id: None,
+        last_error: None,
+    };
+    if let Err(e) = backend.store.save_schedule(&dream_schedule) {
         return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Failed to stop continuous dreaming"
+            "error": format!("Failed to save schedule: {}", e)
         }));
     }
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "Continuous dreaming stopped",
-        "dream_id": query.id
-    }))
+    let response = ScheduleResponse::new(dream_schedule.clone());
+    backend.spawn_schedule(dream_schedule, cron);
+
+    HttpResponse::Created().json(response)
+}
+
+async fn list_schedules(backend: web::Data<Arc<Backend>>) -> impl Responder {
+    match backend.store.list_schedules() {
+        Ok(schedules) => HttpResponse::Ok().json(
+            schedules
+                .into_iter()
+                .map(ScheduleResponse::new)
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to load schedules: {}", e)
+        })),
+    }
+}
+
+async fn delete_schedule(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let schedule_id = path.into_inner();
+
+    match backend.store.delete_schedule(&schedule_id) {
+        Ok(true) => {
+            if let Some(cancel) = backend.schedules_lock().remove(&schedule_id) {
+                cancel.cancel();
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Ok(false) => {
+            HttpResponse::NotFound().json(serde_json::json!({ "error": "Schedule not found" }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to delete schedule: {}", e)
+        })),
+    }
 }
 
 async fn get_nft_metadata(
     backend: web::Data<Arc<Backend>>,
-    path: web::Path<(String)>,
+    path: web::Path<String>,
 ) -> impl Responder {
     let token_id = path.into_inner();
-    
+
+    // A minted dream serves the metadata it was minted with, so this URL agrees with its CID
+    if let Ok(Some(nft)) = backend.store.load_nft(&token_id)
+        && let Ok(Some(blob)) = backend.store.load_blob(&nft.metadata_cid)
+    {
+        return HttpResponse::Ok()
+            .content_type(blob.content_type)
+            .body(blob.data);
+    }
+
     // Find the dream with this ID
-    if let Some(cot_arc) = backend.get_dream(&token_id) {
-        let cot = cot_arc.lock().unwrap();
-        
-        let base_url = "http://127.0.0.1:8080";  // For local development
-        
+    if let Some(dream) = backend.get_dream(&token_id) {
         // Build the metadata
-        let steps = cot.get_steps();
-        
-        let metadata = NftMetadata {
-            name: cot.dream_title.clone().unwrap_or(format!("Dream #{}", token_id)),
-            description: format!(
-                "AI Dreamcatcher visualization: {}",
-                cot.dream_theme.clone().unwrap_or_else(|| "Ethereal Dream".to_string())
+        let metadata = backend.nft_metadata(
+            &dream.snapshot(),
+            format!(
+                "{}/api/dreams/{}/svg",
+                backend.config.server.base_url, token_id
             ),
-            image: format!("{}/api/dreams/{}/svg", base_url, token_id),
-            external_url: Some(format!("{}/dreams/{}", base_url, token_id)),
-            attributes: vec![
-                NftAttribute {
-                    trait_type: "Theme".to_string(),
-                    value: cot.dream_theme.clone().unwrap_or_else(|| "Unknown".to_string()),
-                },
-                NftAttribute {
-                    trait_type: "Steps Count".to_string(),
-                    value: steps.len().to_string(),
-                },
-                NftAttribute {
-                    trait_type: "Anchored".to_string(),
-                    value: steps.iter().any(|s| s.tx_hash.is_some()).to_string(),
-                },
-            ],
-        };
-        
+        );
+
         // Return metadata with proper JSON content type
         HttpResponse::Ok()
             .content_type("application/json")
@@ -755,3 +1931,123 @@ async fn get_nft_metadata(
         HttpResponse::NotFound().json(serde_json::json!({ "error": "Dream not found" }))
     }
 }
+
+// Serve content-addressed SVGs and metadata by CID, like a local IPFS gateway
+async fn get_ipfs_blob(
+    backend: web::Data<Arc<Backend>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let cid = path.into_inner();
+
+    match backend.store.load_blob(&cid) {
+        Ok(Some(blob)) => HttpResponse::Ok()
+            .content_type(blob.content_type)
+            .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+            .body(blob.data),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({ "error": "Blob not found" })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to load blob: {}", e)
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::chain::ChainConfig;
+    use crate::backend::storage::SqliteStore;
+    use actix_web::test;
+
+    // A mock-model backend with a signing key but no node behind it
+    fn backend(store: Arc<SqliteStore>, nft_contract: Option<&str>) -> Arc<Backend> {
+        let chain = ChainClient::new(&ChainConfig {
+            rpc_url: "http://127.0.0.1:1".to_string(),
+            private_key: "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+                .to_string(),
+            anchor_contract: None,
+            nft_contract: nft_contract.map(str::to_string),
+        })
+        .unwrap();
+        let provider = ModelProvider::Mock {
+            seed: 42,
+            rules: Arc::new(Vec::new()),
+        };
+        Arc::new(
+            Backend::new(
+                Config::default(),
+                provider,
+                store,
+                Some(Arc::new(chain)),
+                None,
+                None,
+            )
+            .unwrap(),
+        )
+    }
+
+    async fn request_voucher(backend: Arc<Backend>, dream_id: &str, wallet: &str) -> u16 {
+        let app = test::init_service(App::new().app_data(web::Data::new(backend)).route(
+            "/api/dreams/{id}/mint-voucher",
+            web::post().to(create_mint_voucher),
+        ))
+        .await;
+        let request = test::TestRequest::post()
+            .uri(&format!("/api/dreams/{}/mint-voucher", dream_id))
+            .set_json(serde_json::json!({ "wallet": wallet }))
+            .to_request();
+        test::call_service(&app, request).await.status().as_u16()
+    }
+
+    #[actix_web::test]
+    async fn invalid_wallet_is_rejected_before_publishing() {
+        let store = Arc::new(SqliteStore::open(":memory:").unwrap());
+        let backend = backend(store.clone(), None);
+        let dream_id = backend.create_dream("rivers").await.unwrap();
+
+        assert_eq!(
+            request_voucher(backend, &dream_id, "not-a-wallet").await,
+            400
+        );
+        // Publishing renders and stores the SVG first
+        assert_eq!(store.load_svg(&dream_id).unwrap(), None);
+    }
+
+    #[actix_web::test]
+    async fn valid_wallet_gets_a_voucher_for_the_published_dream() {
+        let store = Arc::new(SqliteStore::open(":memory:").unwrap());
+        let backend = backend(store.clone(), None);
+        let dream_id = backend.create_dream("rivers").await.unwrap();
+
+        let wallet = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+        assert_eq!(request_voucher(backend, &dream_id, wallet).await, 200);
+        assert!(store.load_svg(&dream_id).unwrap().is_some());
+    }
+
+    async fn request_mint(backend: Arc<Backend>, dream_id: &str, recipient: &str) -> u16 {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(backend))
+                .route("/api/dreams/{id}/nft", web::post().to(mint_nft)),
+        )
+        .await;
+        let request = test::TestRequest::post()
+            .uri(&format!("/api/dreams/{}/nft", dream_id))
+            .set_json(serde_json::json!({ "recipient": recipient }))
+            .to_request();
+        test::call_service(&app, request).await.status().as_u16()
+    }
+
+    #[actix_web::test]
+    async fn a_mint_the_node_cannot_take_is_a_bad_gateway() {
+        let store = Arc::new(SqliteStore::open(":memory:").unwrap());
+        let backend = backend(store, Some("0x5FbDB2315678afecb367f032d93F642f64180aa3"));
+        let dream_id = backend.create_dream("rivers").await.unwrap();
+
+        let recipient = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+        assert_eq!(
+            request_mint(backend.clone(), &dream_id, recipient).await,
+            502
+        );
+        assert_eq!(request_mint(backend, &dream_id, "not-a-wallet").await, 400);
+    }
+}
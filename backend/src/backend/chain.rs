@@ -0,0 +1,287 @@
+use crate::backend::config::ChainSettings;
+use alloy::network::{EthereumWallet, TransactionBuilder};
+use alloy::primitives::{Address, B256, Bytes, keccak256};
+use alloy::providers::{DynProvider, Provider, ProviderBuilder};
+use alloy::rpc::types::{TransactionReceipt, TransactionRequest};
+use alloy::signers::SignerSync;
+use alloy::signers::local::PrivateKeySigner;
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolValue};
+use anyhow::Result;
+use std::time::Duration;
+
+sol! {
+    // sense721/src/DreamAnchor.sol
+    interface IDreamAnchor {
+        function anchor(bytes32 digest) external;
+    }
+
+    // sense721/src/DreamNFT.sol
+    interface IDreamNFT {
+        event DreamNFTMinted(uint256 indexed tokenId, address indexed owner, string tokenURI);
+
+        function mintDream(address recipient, string memory tokenURI) external returns (uint256);
+    }
+}
+
+// How long to wait for a submitted transaction to be mined
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone)]
+pub struct AnchorReceipt {
+    pub tx_hash: String,
+    pub block_number: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MintReceipt {
+    pub token_id: String,
+    pub owner: String,
+    pub tx_hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MintVoucher {
+    pub signature: String,
+    pub signer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainConfig {
+    pub rpc_url: String,
+    pub private_key: String,
+    pub anchor_contract: Option<String>,
+    pub nft_contract: Option<String>,
+}
+
+impl ChainConfig {
+    // The chain is enabled once an RPC URL is configured and CHAIN_PRIVATE_KEY is set; an
+    // anchor contract routes anchors through DreamAnchor and an NFT contract enables minting
+    pub fn from_settings(settings: &ChainSettings) -> Option<Self> {
+        let (Some(rpc_url), Ok(private_key)) =
+            (settings.rpc_url.clone(), std::env::var("CHAIN_PRIVATE_KEY"))
+        else {
+            return None;
+        };
+
+        Some(Self {
+            rpc_url,
+            private_key,
+            anchor_contract: settings.anchor_contract.clone(),
+            nft_contract: settings.nft_contract.clone(),
+        })
+    }
+}
+
+pub fn parse_address(address: &str, what: &str) -> Result<Address> {
+    address
+        .parse::<Address>()
+        .map_err(|e| anyhow::anyhow!("Invalid {} address {}: {}", what, address, e))
+}
+
+// Signs and submits transactions to an EVM chain over JSON-RPC with a locally held key
+pub struct ChainClient {
+    provider: DynProvider,
+    signer: PrivateKeySigner,
+    signer_address: Address,
+    anchor_contract: Option<Address>,
+    nft_contract: Option<Address>,
+}
+
+impl ChainClient {
+    pub fn new(config: &ChainConfig) -> Result<Self> {
+        let signer: PrivateKeySigner = config
+            .private_key
+            .trim_start_matches("0x")
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid chain private key: {}", e))?;
+        let signer_address = signer.address();
+
+        let provider =
+            ProviderBuilder::new()
+                .wallet(EthereumWallet::from(signer.clone()))
+                .connect_http(config.rpc_url.parse().map_err(|e| {
+                    anyhow::anyhow!("Invalid chain RPC URL {}: {}", config.rpc_url, e)
+                })?)
+                .erased();
+
+        let anchor_contract = config
+            .anchor_contract
+            .as_deref()
+            .map(|address| parse_address(address, "anchor contract"))
+            .transpose()?;
+        let nft_contract = config
+            .nft_contract
+            .as_deref()
+            .map(|address| parse_address(address, "NFT contract"))
+            .transpose()?;
+
+        Ok(Self {
+            provider,
+            signer,
+            signer_address,
+            anchor_contract,
+            nft_contract,
+        })
+    }
+
+    pub fn can_mint(&self) -> bool {
+        self.nft_contract.is_some()
+    }
+
+    pub fn nft_contract(&self) -> Option<String> {
+        self.nft_contract.map(|address| address.to_string())
+    }
+
+    // Authorize `minter` to call `DreamNFT.mintWithSignature(tokenURI, signature)` and pay for
+    // the mint themselves. The contract expects the owner's EIP-191 signature over
+    // keccak256(abi.encodePacked(minter, tokenURI)); nothing is sent on chain here.
+    pub fn sign_mint_voucher(&self, minter: &str, token_uri: &str) -> Result<MintVoucher> {
+        let minter = parse_address(minter, "wallet")?;
+        let message_hash = keccak256((minter, token_uri.to_string()).abi_encode_packed());
+
+        let signature = self.signer.sign_message_sync(message_hash.as_slice())?;
+
+        Ok(MintVoucher {
+            signature: format!("0x{}", hex::encode(signature.as_bytes())),
+            signer: self.signer_address.to_string(),
+        })
+    }
+
+    // Record a 32-byte digest on chain: either `DreamAnchor.anchor(digest)` or, without an
+    // anchor contract, a zero-value transaction to ourselves carrying the digest as calldata
+    pub async fn anchor(&self, digest: B256) -> Result<AnchorReceipt> {
+        let tx = match self.anchor_contract {
+            Some(contract) => TransactionRequest::default()
+                .with_to(contract)
+                .with_input(IDreamAnchor::anchorCall { digest }.abi_encode()),
+            None => TransactionRequest::default()
+                .with_to(self.signer_address)
+                .with_input(Bytes::copy_from_slice(digest.as_slice())),
+        };
+
+        let receipt = self.send(tx).await?;
+
+        Ok(AnchorReceipt {
+            tx_hash: receipt.transaction_hash.to_string(),
+            block_number: receipt.block_number,
+        })
+    }
+
+    // Call `DreamNFT.mintDream(recipient, tokenURI)` as the contract owner and read the
+    // token ID back from the `DreamNFTMinted` event. Mints to the signer when no recipient is given.
+    pub async fn mint_dream(
+        &self,
+        recipient: Option<&str>,
+        token_uri: &str,
+    ) -> Result<MintReceipt> {
+        let contract = self
+            .nft_contract
+            .ok_or_else(|| anyhow::anyhow!("DreamNFT contract address is not configured"))?;
+        let recipient = match recipient {
+            Some(address) => parse_address(address, "recipient")?,
+            None => self.signer_address,
+        };
+
+        let call = IDreamNFT::mintDreamCall {
+            recipient,
+            tokenURI: token_uri.to_string(),
+        };
+        let tx = TransactionRequest::default()
+            .with_to(contract)
+            .with_input(call.abi_encode());
+
+        let receipt = self.send(tx).await?;
+
+        let minted = receipt
+            .logs()
+            .iter()
+            .filter(|log| log.address() == contract)
+            .find_map(|log| log.log_decode::<IDreamNFT::DreamNFTMinted>().ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No DreamNFTMinted event in transaction {}",
+                    receipt.transaction_hash
+                )
+            })?;
+
+        Ok(MintReceipt {
+            token_id: minted.inner.data.tokenId.to_string(),
+            owner: minted.inner.data.owner.to_string(),
+            tx_hash: receipt.transaction_hash.to_string(),
+        })
+    }
+
+    // Submit a transaction and wait for a successful receipt
+    async fn send(&self, tx: TransactionRequest) -> Result<TransactionReceipt> {
+        let receipt = self
+            .provider
+            .send_transaction(tx)
+            .await?
+            .with_timeout(Some(RECEIPT_TIMEOUT))
+            .get_receipt()
+            .await?;
+
+        if !receipt.status() {
+            return Err(anyhow::anyhow!(
+                "Transaction {} reverted",
+                receipt.transaction_hash
+            ));
+        }
+
+        Ok(receipt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::Signature;
+    use std::str::FromStr;
+
+    // The first account of a default anvil or hardhat node
+    const OWNER_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const OWNER: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+    const MINTER: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+    const TOKEN_URI: &str = "ipfs://bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku";
+
+    fn client() -> ChainClient {
+        ChainClient::new(&ChainConfig {
+            rpc_url: "http://127.0.0.1:8545".to_string(),
+            private_key: OWNER_KEY.to_string(),
+            anchor_contract: None,
+            nft_contract: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn voucher_recovers_to_the_owner() {
+        let voucher = client().sign_mint_voucher(MINTER, TOKEN_URI).unwrap();
+        assert_eq!(voucher.signer, OWNER);
+
+        // What DreamNFT.mintWithSignature recovers from the signature
+        let message_hash = keccak256(
+            (MINTER.parse::<Address>().unwrap(), TOKEN_URI.to_string()).abi_encode_packed(),
+        );
+        let signature = Signature::from_str(&voucher.signature).unwrap();
+        let signer = signature
+            .recover_address_from_msg(message_hash.as_slice())
+            .unwrap();
+        assert_eq!(signer, OWNER.parse::<Address>().unwrap());
+    }
+
+    #[test]
+    fn voucher_is_bound_to_the_minter() {
+        let client = client();
+        let voucher = client.sign_mint_voucher(MINTER, TOKEN_URI).unwrap();
+        let other = client.sign_mint_voucher(OWNER, TOKEN_URI).unwrap();
+        assert_ne!(voucher.signature, other.signature);
+
+        assert!(
+            client
+                .sign_mint_voucher("not-an-address", TOKEN_URI)
+                .is_err()
+        );
+    }
+}
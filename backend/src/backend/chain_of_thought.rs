@@ -1,8 +1,26 @@
+use crate::backend::chain::{ChainClient, MintReceipt};
+use crate::backend::conclusion;
+use crate::backend::events::{DreamEventKind, EventSink};
+use crate::backend::pipeline::{self, StepKind, StepSpec};
+use crate::backend::provider::{ReportsUsage, TokenUsage};
+use crate::backend::structured::{self, StructuredAnswer, StructuredOutputError};
 use alith::{Agent, Completion};
+use alloy::primitives::{B256, keccak256};
+use alloy::sol_types::SolValue;
 use anyhow::{Error, Result};
-use chrono::Utc;
-use serde::Serialize;
-use std::{collections::VecDeque, fmt::Write};
+use chrono::{SecondsFormat, Utc};
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    fmt::Write,
+    sync::Arc,
+    time::Instant,
+};
 
 pub struct ChainOfThought<M: Completion> {
     agent: Agent<M>,
@@ -10,116 +28,1716 @@ pub struct ChainOfThought<M: Completion> {
     current_step: usize,
     pub dream_title: Option<String>,
     pub dream_theme: Option<String>,
+    // Steps the agent planned for this dream, when it planned them itself
+    pub plan: Option<Vec<StepSpec>>,
+    events: Option<EventSink>,
+    context: Option<ContextWindow>,
+    // Name of the model behind the agent, recorded with each run of a step
+    model: Option<String>,
+    // Model calls of the steps being run, moved onto each step once its run is recorded
+    run_calls: HashMap<usize, Vec<ModelCall>>,
+    concurrency: Option<Concurrency<M>>,
+    // Agents built for earlier concurrent steps, kept for the next ones
+    workers: Vec<Agent<M>>,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+// Builds the agent of the worker with the given index. Each worker needs an agent of its own,
+// since an agent runs one completion at a time.
+pub type AgentFactory<M> = Arc<dyn Fn(usize) -> Result<Agent<M>> + Send + Sync>;
+
+// How many independent steps may be processed at once, and where their agents come from
+pub struct Concurrency<M: Completion> {
+    pub limit: usize,
+    pub factory: AgentFactory<M>,
+}
+
+// How much of the chain so far is fed into each new step's prompt
+#[derive(Debug, Clone, Copy)]
+pub struct ContextWindow {
+    // Most recent concluded steps to include
+    pub max_steps: usize,
+    // Character budget for the whole context block; older conclusions are cut first
+    pub max_chars: usize,
+}
+
+// Character budget of the context block when the config doesn't set one
+pub const DEFAULT_CONTEXT_CHARS: usize = 2000;
+
+// What a step concluded, typed by the kind of reasoning that produced it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Conclusion {
+    Open {
+        text: String,
+    },
+    Boolean {
+        value: bool,
+    },
+    Choice {
+        options: Vec<String>,
+        selected: String,
+    },
+    Numeric {
+        min: i32,
+        max: i32,
+        value: i32,
+    },
+}
+
+// The plain-text form used in prompts and anchored digests ("TRUE", "7", the chosen option...)
+impl fmt::Display for Conclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conclusion::Open { text } => write!(f, "{}", text),
+            Conclusion::Boolean { value } => write!(f, "{}", if *value { "TRUE" } else { "FALSE" }),
+            Conclusion::Choice { selected, .. } => write!(f, "{}", selected),
+            Conclusion::Numeric { value, .. } => write!(f, "{}", value),
+        }
+    }
+}
+
+// Why work on a dream or one of its steps failed. The checks that need no model run before
+// the agent is prompted, so a request for a missing or finished step costs nothing.
+#[derive(Debug)]
+pub enum ChainOfThoughtError {
+    StepNotFound {
+        step_id: usize,
+    },
+    // Processing again goes through a rerun, which keeps the earlier run in the history
+    AlreadyProcessed {
+        step_id: usize,
+    },
+    // Anchoring and rerunning need a step that has been run
+    NotProcessed {
+        step_id: usize,
+    },
+    // The step's anchor still matches it, so anchoring again would only spend gas
+    AlreadyAnchored {
+        step_id: usize,
+    },
+    // A dependency that doesn't exist or closes a cycle
+    InvalidDependency {
+        step_id: usize,
+        reason: String,
+    },
+    NotABranch {
+        step_id: usize,
+    },
+    // Other steps build on it or it's the branch taken, so it can't be deleted
+    StepInUse {
+        step_id: usize,
+        reason: String,
+    },
+    // A typed step, or the plan or branches asked for, never came back as a valid answer
+    ParseFailed(StructuredOutputError),
+    // The model call itself failed; without a step it was made for the dream as a whole
+    LlmFailed {
+        step_id: Option<usize>,
+        reason: String,
+    },
+    Anchoring {
+        step_id: usize,
+        reason: String,
+    },
+    // The mint transaction failed, reverted or never came back with a token
+    Minting {
+        reason: String,
+    },
+}
+
+impl fmt::Display for ChainOfThoughtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StepNotFound { step_id } => write!(f, "Step {} not found", step_id),
+            Self::AlreadyProcessed { step_id } => write!(
+                f,
+                "Step {} has already been processed; rerun it to process it again",
+                step_id
+            ),
+            Self::NotProcessed { step_id } => {
+                write!(f, "Step {} hasn't been processed yet", step_id)
+            }
+            Self::AlreadyAnchored { step_id } => write!(
+                f,
+                "Step {} is already anchored and hasn't changed since",
+                step_id
+            ),
+            Self::InvalidDependency { reason, .. } | Self::StepInUse { reason, .. } => {
+                write!(f, "{}", reason)
+            }
+            Self::NotABranch { step_id } => write!(f, "Step {} is not a branch", step_id),
+            Self::ParseFailed(e) => write!(f, "{}", e),
+            Self::LlmFailed {
+                step_id: Some(step_id),
+                reason,
+            } => write!(f, "The model failed on step {}: {}", step_id, reason),
+            Self::LlmFailed {
+                step_id: None,
+                reason,
+            } => write!(f, "The model failed: {}", reason),
+            Self::Anchoring { step_id, reason } => {
+                write!(f, "Failed to anchor step {}: {}", step_id, reason)
+            }
+            Self::Minting { reason } => write!(f, "Failed to mint the dream: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ChainOfThoughtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ParseFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Fewest and most alternative continuations a step may branch into
+pub const MIN_BRANCHES: usize = 2;
+pub const MAX_BRANCHES: usize = 5;
+
+// Highest score the agent gives a branch, from 1
+const MAX_BRANCH_SCORE: i32 = 10;
+
+// Continuations to branch into, three unless asked for
+pub fn branch_count(branches: Option<usize>) -> Result<usize, String> {
+    match branches.unwrap_or(3) {
+        count if (MIN_BRANCHES..=MAX_BRANCHES).contains(&count) => Ok(count),
+        _ => Err(format!(
+            "branches must be between {} and {}",
+            MIN_BRANCHES, MAX_BRANCHES
+        )),
+    }
+}
+
+// An alternative way to continue from a step, as the agent proposes it
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct BranchProposal {
+    /// Short name of the continuation
+    description: String,
+    /// The question the continuation explores
+    prompt: String,
+}
+
+// A step and the branches that fork from it, as exposed alongside a dream's flat step list
+#[derive(Debug, Clone, Serialize)]
+pub struct StepNode {
+    pub step_id: usize,
+    pub desc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chosen: Option<bool>,
+    pub children: Vec<StepNode>,
+}
+
+// The steps of a dream as a forest: steps that don't branch from another are roots, in order,
+// and each branch hangs under the step it forks from. None when nothing has branched.
+pub fn step_tree(steps: &[ReasoningStep]) -> Option<Vec<StepNode>> {
+    fn node(step: &ReasoningStep, steps: &[ReasoningStep]) -> StepNode {
+        StepNode {
+            step_id: step.step_id,
+            desc: step.desc.clone(),
+            score: step.score,
+            chosen: step.chosen,
+            children: steps
+                .iter()
+                .filter(|s| s.parent == Some(step.step_id))
+                .map(|s| node(s, steps))
+                .collect(),
+        }
+    }
+
+    if steps.iter().all(|s| s.parent.is_none()) {
+        return None;
+    }
+    Some(
+        steps
+            .iter()
+            .filter(|s| s.parent.is_none())
+            .map(|s| node(s, steps))
+            .collect(),
+    )
+}
+
+// What a critique replies when a draft needs no revision
+const NO_ISSUES: &str = "No issues";
+
+// Most critique-and-revise rounds an open-ended step may run
+pub const MAX_REFLECT_ROUNDS: usize = 3;
+
+// Critique-and-revise rounds to run, none unless asked for
+pub fn reflect_rounds(reflect: Option<usize>) -> Result<usize, String> {
+    match reflect {
+        None => Ok(0),
+        Some(rounds) if (1..=MAX_REFLECT_ROUNDS).contains(&rounds) => Ok(rounds),
+        Some(_) => Err(format!(
+            "reflect must be between 1 and {}",
+            MAX_REFLECT_ROUNDS
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionKind {
+    Draft,
+    Critique,
+}
+
+// One draft of an open-ended step's reasoning, or the agent's critique of the draft before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    // 0 for the first draft; round N holds the Nth critique and the draft revised from it
+    pub round: usize,
+    pub kind: RevisionKind,
+    pub text: String,
+    pub created_at: String,
+}
+
+impl Revision {
+    fn new(round: usize, kind: RevisionKind, text: String) -> Self {
+        Self {
+            round,
+            kind,
+            text,
+            created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+// How the sampled answers of a typed step split when it was concluded by self-consistency vote
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Votes {
+    // Valid answers the conclusion was voted from
+    pub samples: usize,
+    // Samples per answer, keyed by the answer's plain-text form
+    pub distribution: BTreeMap<String, usize>,
+    // Share of the samples that gave the concluded answer, from 0 to 1
+    pub agreement: f64,
+}
+
+// One exchange with the model while running a step: exactly what it was sent, context
+// included, and what it replied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelCall {
+    pub prompt: String,
+    pub response: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryAction {
+    Processed,
+    Edited,
+}
+
+// One entry of a step's history: a run of the step, or an edit of what it asks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub action: HistoryAction,
+    pub description: String,
+    // The question the step asked, or asks after the edit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    // For a run, the model that answered and the reasoning and conclusion it gave
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<Conclusion>,
+    pub created_at: String,
+}
+
+impl HistoryEntry {
+    fn new(action: HistoryAction, step: &ReasoningStep) -> Self {
+        Self {
+            action,
+            description: step.desc.clone(),
+            prompt: step.spec.as_ref().and_then(|spec| spec.prompt.clone()),
+            model: None,
+            response: None,
+            conclusion: None,
+            created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ReasoningStep {
     pub step_id: usize,
     pub desc: String,
     pub reasoning: String,
-    pub conclusion: Option<String>,
+    pub conclusion: Option<Conclusion>,
+    // The first sentence of an open step's conclusion, for listings and titles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    // Self-reported confidence (0-1) of typed steps
+    pub confidence: Option<f64>,
+    // Set when the conclusion was voted from several samples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub votes: Option<Votes>,
+    // Drafts and critiques of a step processed with reflection, oldest first; the last draft
+    // is the step's reasoning
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<Revision>,
+    // The step this one branches from, when it's one of several alternative continuations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    // How well the agent rated this branch against its siblings, from 1 to 10
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i32>,
+    // Whether this branch is the path taken; unset until its siblings are compared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chosen: Option<bool>,
+    // The steps this one builds on. When unset the step builds on the steps before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<usize>>,
+    // How the step was last run, so it can be rerun
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spec: Option<StepSpec>,
+    // Runs and edits of the step, oldest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>,
+    // Every model call of the step's latest run, in order: samples, retries, critiques and
+    // revisions included
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<ModelCall>,
     pub tx_hash: Option<String>,
+    pub block_number: Option<u64>,
+    pub anchor_digest: Option<String>,
+    // Set once the step no longer matches the digest it was anchored with
+    pub stale: bool,
+    // Earlier steps whose conclusions were included in this step's prompt
+    pub context_steps: Vec<usize>,
+}
+
+impl ReasoningStep {
+    pub fn new(step_id: usize, desc: String) -> Self {
+        Self {
+            step_id,
+            desc,
+            reasoning: String::new(),
+            conclusion: None,
+            summary: None,
+            confidence: None,
+            votes: None,
+            revisions: Vec::new(),
+            parent: None,
+            score: None,
+            chosen: None,
+            depends_on: None,
+            spec: None,
+            history: Vec::new(),
+            calls: Vec::new(),
+            tx_hash: None,
+            block_number: None,
+            anchor_digest: None,
+            stale: false,
+            context_steps: Vec::new(),
+        }
+    }
+
+    // keccak256 of (desc, prompts, reasoning, conclusion) ABI-encoded as one tuple value, i.e.
+    // Solidity's keccak256(abi.encode(step)) for a struct of those fields, where `prompts` is
+    // the string[] of what each model call was sent. Anyone holding the step can recompute the
+    // digest and compare it with the anchored calldata. Steps without recorded calls keep the
+    // earlier (desc, reasoning, conclusion) tuple.
+    pub fn digest(&self) -> B256 {
+        let conclusion = self
+            .conclusion
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        if self.calls.is_empty() {
+            return keccak256((self.desc.clone(), self.reasoning.clone(), conclusion).abi_encode());
+        }
+
+        let prompts: Vec<String> = self.calls.iter().map(|call| call.prompt.clone()).collect();
+        keccak256(
+            (
+                self.desc.clone(),
+                prompts,
+                self.reasoning.clone(),
+                conclusion,
+            )
+                .abi_encode(),
+        )
+    }
+
+    // Whether the step has been run, as opposed to only added with a prompt
+    pub fn has_run(&self) -> bool {
+        self.conclusion.is_some()
+            || self
+                .history
+                .iter()
+                .any(|entry| entry.action == HistoryAction::Processed)
+    }
+
+    // Whether the step was anchored and has changed since
+    fn anchor_outdated(&self) -> bool {
+        self.anchor_digest
+            .as_ref()
+            .is_some_and(|digest| *digest != self.digest().to_string())
+    }
+}
+
+// Why `step_id` can't be deleted, if it can't: other steps build on it or branch from it, or
+// it's the branch taken among its siblings
+pub fn deletion_blocker<'a>(
+    steps: impl IntoIterator<Item = &'a ReasoningStep>,
+    step_id: usize,
+) -> Option<String> {
+    let steps: Vec<&ReasoningStep> = steps.into_iter().collect();
+    let dependents: Vec<String> = steps
+        .iter()
+        .filter(|s| {
+            s.parent == Some(step_id) || s.depends_on.as_ref().is_some_and(|d| d.contains(&step_id))
+        })
+        .map(|s| s.step_id.to_string())
+        .collect();
+    match dependents.as_slice() {
+        [] => {}
+        [dependent] => {
+            return Some(format!(
+                "Step {} builds on step {}; delete it first",
+                dependent, step_id
+            ));
+        }
+        _ => {
+            return Some(format!(
+                "Steps {} build on step {}; delete them first",
+                dependents.join(", "),
+                step_id
+            ));
+        }
+    }
+
+    let step = steps.iter().find(|s| s.step_id == step_id)?;
+    let has_siblings = steps
+        .iter()
+        .any(|s| s.step_id != step_id && s.parent.is_some() && s.parent == step.parent);
+    if step.chosen == Some(true) && has_siblings {
+        return Some(format!(
+            "Step {} is the branch taken; choose another branch before deleting it",
+            step_id
+        ));
+    }
+    None
 }
 
-impl ReasoningStep {
-    pub fn new(step_id: usize, desc: String) -> Self {
-        Self {
-            step_id,
-            desc,
-            reasoning: String::new(),
-            conclusion: None,
-            tx_hash: None,
+impl<M: Completion + ReportsUsage> ChainOfThought<M> {
+    pub fn new(agent: Agent<M>) -> Self {
+        Self {
+            reasoning_steps: VecDeque::new(),
+            agent,
+            current_step: 0,
+            dream_title: None,
+            dream_theme: None,
+            plan: None,
+            events: None,
+            context: None,
+            model: None,
+            run_calls: HashMap::new(),
+            concurrency: None,
+            workers: Vec::new(),
+        }
+    }
+
+    // Rebuild a chain from persisted state, continuing step numbering after the last step
+    pub fn restore(
+        agent: Agent<M>,
+        title: Option<String>,
+        theme: Option<String>,
+        steps: Vec<ReasoningStep>,
+    ) -> Self {
+        let current_step = steps.iter().map(|s| s.step_id + 1).max().unwrap_or(0);
+        Self {
+            reasoning_steps: steps.into(),
+            agent,
+            current_step,
+            dream_title: title,
+            dream_theme: theme,
+            plan: None,
+            events: None,
+            context: None,
+            model: None,
+            run_calls: HashMap::new(),
+            concurrency: None,
+            workers: Vec::new(),
+        }
+    }
+
+    // A copy of this chain on another agent, to process one step alongside others
+    fn worker(&self, agent: Agent<M>) -> Self {
+        Self {
+            agent,
+            reasoning_steps: self.reasoning_steps.clone(),
+            current_step: self.current_step,
+            dream_title: self.dream_title.clone(),
+            dream_theme: self.dream_theme.clone(),
+            plan: None,
+            events: self.events.clone(),
+            context: self.context,
+            model: self.model.clone(),
+            run_calls: HashMap::new(),
+            concurrency: None,
+            workers: Vec::new(),
+        }
+    }
+
+    // Report step progress to SSE subscribers
+    pub fn set_events(&mut self, events: EventSink) {
+        self.events = Some(events);
+    }
+
+    fn emit(&self, kind: DreamEventKind) {
+        if let Some(events) = &self.events {
+            events.emit(kind);
+        }
+    }
+
+    // Feed earlier conclusions into each step's prompt; without this every step is
+    // reasoned about in isolation
+    pub fn set_context(&mut self, context: ContextWindow) {
+        self.context = Some(context);
+    }
+
+    pub fn set_model(&mut self, model: &str) {
+        self.model = Some(model.to_string());
+    }
+
+    // Let steps that don't depend on each other be processed side by side
+    pub fn set_concurrency(&mut self, concurrency: Concurrency<M>) {
+        self.concurrency = Some(concurrency);
+    }
+
+    // Summarize the concluded steps `step_id` builds on, returning the prompt preamble and the
+    // IDs of the steps it covers. A step that declares `depends_on` gets exactly those steps,
+    // even with the context window off; any other step gets the steps before it, within the
+    // context window.
+    fn build_context(&self, step_id: usize) -> (String, Vec<usize>) {
+        let declared = self
+            .reasoning_steps
+            .iter()
+            .find(|s| s.step_id == step_id)
+            .and_then(|s| s.depends_on.clone());
+        let (window, candidates) = match declared {
+            Some(depends_on) => (
+                ContextWindow {
+                    max_steps: depends_on.len(),
+                    max_chars: self
+                        .context
+                        .map_or(DEFAULT_CONTEXT_CHARS, |window| window.max_chars),
+                },
+                depends_on
+                    .iter()
+                    .rev()
+                    .filter_map(|id| self.reasoning_steps.iter().find(|s| s.step_id == *id))
+                    .collect(),
+            ),
+            None => match self.context {
+                Some(window) => (window, self.lineage(step_id)),
+                None => return (String::new(), Vec::new()),
+            },
+        };
+
+        // Walk back from the newest step so the budget favours recent conclusions
+        let mut budget = window.max_chars;
+        let mut included = Vec::new();
+        for step in candidates
+            .into_iter()
+            .filter(|s| s.conclusion.is_some())
+            .take(window.max_steps)
+        {
+            let line = match &step.conclusion {
+                Some(conclusion) => format!("- {}: {}", step.desc, conclusion),
+                None => format!("- {}", step.desc),
+            };
+            let len = line.chars().count();
+            if len <= budget {
+                budget -= len;
+                included.push((step.step_id, line));
+            } else {
+                if budget > 3 {
+                    let cut: String = line.chars().take(budget - 3).collect();
+                    included.push((step.step_id, format!("{}...", cut)));
+                }
+                break;
+            }
+        }
+
+        if included.is_empty() {
+            return (String::new(), Vec::new());
+        }
+
+        included.reverse();
+        let (ids, lines): (Vec<usize>, Vec<String>) = included.into_iter().unzip();
+        let preamble = format!(
+            "Earlier steps in this chain of thought concluded:\n{}\n\nBuild on these conclusions where relevant.\n\n",
+            lines.join("\n")
+        );
+
+        (preamble, ids)
+    }
+
+    // The steps `step_id` builds on, newest first: the steps a branch forks from, then the
+    // steps before the root of that chain, leaving out branches that weren't taken
+    fn lineage(&self, step_id: usize) -> Vec<&ReasoningStep> {
+        let find = |id: usize| self.reasoning_steps.iter().find(|s| s.step_id == id);
+
+        let mut lineage = Vec::new();
+        let mut root = step_id;
+        while let Some(parent) = find(root).and_then(|s| s.parent).and_then(find) {
+            lineage.push(parent);
+            root = parent.step_id;
+        }
+
+        let position = self
+            .reasoning_steps
+            .iter()
+            .position(|s| s.step_id == root)
+            .unwrap_or(self.reasoning_steps.len());
+        lineage.extend(
+            self.reasoning_steps
+                .iter()
+                .take(position)
+                .rev()
+                .filter(|s| !self.off_path(s)),
+        );
+        lineage
+    }
+
+    // Whether a step is, or descends from, a branch that wasn't taken
+    fn off_path(&self, step: &ReasoningStep) -> bool {
+        let mut current = Some(step);
+        while let Some(step) = current {
+            if step.chosen == Some(false) {
+                return true;
+            }
+            current = step
+                .parent
+                .and_then(|id| self.reasoning_steps.iter().find(|s| s.step_id == id));
+        }
+        false
+    }
+
+    // Prompt the agent on behalf of a step, forwarding the reasoning as it arrives. Providers
+    // return whole completions, so each completion is currently a single delta.
+    async fn prompt_step(&mut self, step_id: usize, prompt: &str) -> Result<String, Error> {
+        let response = self.complete_step(step_id, prompt).await?;
+        self.emit(DreamEventKind::TokenDelta {
+            step_id,
+            delta: response.clone(),
+        });
+        Ok(response)
+    }
+
+    // Prompt with the step's context but without streaming the reply as the step's reasoning
+    async fn complete_step(&mut self, step_id: usize, prompt: &str) -> Result<String, Error> {
+        let (context, context_steps) = self.build_context(step_id);
+        if let Some(step) = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+        {
+            step.context_steps = context_steps;
+        }
+
+        self.call_model(step_id, &format!("{}{}", context, prompt))
+            .await
+    }
+
+    // Prompt the agent as is and record the call against the step
+    async fn call_model(&mut self, step_id: usize, prompt: &str) -> Result<String, Error> {
+        let prompt = prompt.to_string();
+        let started = Instant::now();
+        let response = self.prompt_agent(Some(step_id), &prompt).await?;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        // Each chain has its agent to itself, so nothing else has prompted the model since
+        let usage = self.agent.model.read().await.last_usage();
+
+        self.run_calls.entry(step_id).or_default().push(ModelCall {
+            prompt,
+            response: response.clone(),
+            model: self.model.clone(),
+            latency_ms,
+            usage,
+        });
+        Ok(response)
+    }
+
+    // Every prompt goes through here, so a failed call is always an LlmFailed
+    async fn prompt_agent(
+        &self,
+        step_id: Option<usize>,
+        prompt: &str,
+    ) -> Result<String, ChainOfThoughtError> {
+        self.agent
+            .prompt(prompt)
+            .await
+            .map_err(|e| ChainOfThoughtError::LlmFailed {
+                step_id,
+                reason: format!("{:#}", e),
+            })
+    }
+
+    fn require_step(&self, step_id: usize) -> Result<&ReasoningStep, ChainOfThoughtError> {
+        self.reasoning_steps
+            .iter()
+            .find(|s| s.step_id == step_id)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })
+    }
+
+    // A step is processed once; after that it's rerun
+    pub fn ensure_unprocessed(&self, step_id: usize) -> Result<(), ChainOfThoughtError> {
+        if self.require_step(step_id)?.has_run() {
+            return Err(ChainOfThoughtError::AlreadyProcessed { step_id });
+        }
+        Ok(())
+    }
+
+    // Forget the calls of an earlier run of the step that never got recorded
+    fn begin_run(&mut self, step_id: usize) {
+        self.run_calls.remove(&step_id);
+    }
+
+    fn conclude(&self, step_id: usize) {
+        if let Some(step) = self.reasoning_steps.iter().find(|s| s.step_id == step_id) {
+            self.emit(DreamEventKind::StepConcluded {
+                step_id,
+                conclusion: step.conclusion.clone(),
+            });
+        }
+    }
+
+    pub fn add_step(&mut self, desc: String) -> usize {
+        self.push_step(desc, None)
+    }
+
+    // Add a step along with the question it asks, kept as an open step's spec until it's run
+    pub fn add_prompted_step(&mut self, desc: String, prompt: String) -> usize {
+        let step_id = self.push_step(desc.clone(), None);
+        if let Some(step) = self.reasoning_steps.back_mut() {
+            step.spec = Some(StepSpec {
+                prompt: Some(prompt),
+                ..StepSpec::open(desc)
+            });
+        }
+        step_id
+    }
+
+    // Add an alternative continuation of `parent`
+    fn add_branch(&mut self, parent: usize, desc: String) -> usize {
+        self.push_step(desc, Some(parent))
+    }
+
+    fn push_step(&mut self, desc: String, parent: Option<usize>) -> usize {
+        let step_id = self.current_step;
+        self.emit(DreamEventKind::StepCreated {
+            step_id,
+            desc: desc.clone(),
+            parent,
+        });
+        let mut step = ReasoningStep::new(step_id, desc);
+        step.parent = parent;
+        self.reasoning_steps.push_back(step);
+        self.current_step += 1;
+        step_id
+    }
+
+    pub async fn set_dream_context(&mut self, theme: &str) -> Result<(), Error> {
+        // Generate a creative title for this dream based on the theme
+        let title_prompt = format!(
+            "Generate a poetic, creative title for an AI's dream visualization about: {}. Respond with ONLY the title, no explanation.",
+            theme
+        );
+
+        let title = self.prompt_agent(None, &title_prompt).await?;
+        self.dream_title = Some(title);
+        self.dream_theme = Some(theme.to_string());
+
+        Ok(())
+    }
+
+    // Theme for a follow-up dream that picks up where this dream's conclusions left off
+    pub async fn next_theme(&self) -> Result<String, Error> {
+        let theme = self.dream_theme.as_deref().unwrap_or("an ethereal dream");
+        let conclusions: String = self
+            .reasoning_steps
+            .iter()
+            .filter(|s| !self.off_path(s))
+            .filter_map(|s| Some(format!("- {}: {}\n", s.desc, s.conclusion.as_ref()?)))
+            .collect();
+
+        let theme_prompt = format!(
+            "An AI dream about {} reached these conclusions:\n{}\nSuggest the theme of the next dream, continuing from where these conclusions leave off. Respond with ONLY the theme, no explanation.",
+            theme, conclusions
+        );
+
+        let next = self.prompt_agent(None, &theme_prompt).await?;
+        let next = next.trim().trim_matches('"').trim();
+        if next.is_empty() {
+            return Ok(theme.to_string());
+        }
+        Ok(next.to_string())
+    }
+
+    // Have the agent plan `count` steps exploring the dream's theme, re-prompting with the
+    // rejection reason until the plan parses and validates or the attempts run out
+    pub async fn plan_steps(&mut self, count: usize) -> Result<Vec<StepSpec>, Error> {
+        let theme = self.dream_theme.as_deref().unwrap_or("an ethereal dream");
+        let plan_prompt = format!(
+            "Plan a chain of thought of exactly {} reasoning steps that explores this theme: {}\n\n\
+            Give each step a short `description`, a `prompt` asking one specific question about the theme, and a `kind`: \
+            \"open\" for open-ended analysis, \"boolean\" for a statement to judge TRUE or FALSE, \
+            \"choice\" with 2 to 6 distinct `options` to pick from, or \"numeric\" with integer `min` and `max` bounds. \
+            Use the kinds that suit each question and let later steps build on earlier ones; \
+            a prompt may include {{previous}} where the previous step's conclusion should go.\n\n\
+            Set `answer` to the list of steps.",
+            count, theme
+        );
+        let schema = structured::schema::<Vec<StepSpec>>(|answer| {
+            answer["minItems"] = serde_json::json!(count);
+            answer["maxItems"] = serde_json::json!(count);
+        });
+
+        let steps = self
+            .prompt_structured::<Vec<StepSpec>>(None, &plan_prompt, &schema, |plan| {
+                validate_plan(plan, count)
+            })
+            .await?
+            .answer;
+
+        self.plan = Some(steps.clone());
+        self.emit(DreamEventKind::StepsPlanned {
+            steps: steps.clone(),
+        });
+        Ok(steps)
+    }
+
+    // Declare the steps `step_id` builds on, or with None go back to building on the steps
+    // before it. Rejected if a step it names doesn't exist or it would close a cycle.
+    pub fn set_depends_on(
+        &mut self,
+        step_id: usize,
+        depends_on: Option<Vec<usize>>,
+    ) -> Result<(), Error> {
+        if let Some(dependency) = depends_on
+            .iter()
+            .flatten()
+            .find(|id| **id == step_id || !self.reasoning_steps.iter().any(|s| s.step_id == **id))
+        {
+            let reason = if *dependency == step_id {
+                format!("Step {} can't depend on itself", step_id)
+            } else {
+                format!("Step {} depends on unknown step {}", step_id, dependency)
+            };
+            return Err(ChainOfThoughtError::InvalidDependency { step_id, reason }.into());
+        }
+
+        let step = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
+        let previous = std::mem::replace(&mut step.depends_on, depends_on);
+
+        if let Err(e) = self.validate_graph() {
+            if let Some(step) = self
+                .reasoning_steps
+                .iter_mut()
+                .find(|s| s.step_id == step_id)
+            {
+                step.depends_on = previous;
+            }
+            return Err(ChainOfThoughtError::InvalidDependency {
+                step_id,
+                reason: e.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    // Check that every declared dependency exists and that no step ends up depending on itself
+    pub fn validate_graph(&self) -> Result<(), Error> {
+        let graph: BTreeMap<usize, Vec<usize>> = self
+            .reasoning_steps
+            .iter()
+            .map(|s| (s.step_id, s.depends_on.clone().unwrap_or_default()))
+            .collect();
+
+        for (step_id, depends_on) in &graph {
+            if let Some(unknown) = depends_on.iter().find(|id| !graph.contains_key(id)) {
+                return Err(anyhow::anyhow!(
+                    "Step {} depends on unknown step {}",
+                    step_id,
+                    unknown
+                ));
+            }
+        }
+        match find_cycle(&graph) {
+            Some(cycle) => Err(anyhow::anyhow!(
+                "Steps depend on each other in a cycle: {}",
+                cycle
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Process `jobs` (step IDs with their specs, in the order they were added) as a dependency
+    // graph. A job starts once the jobs it depends on are done; a step without `depends_on`
+    // waits for the job before it, as if the jobs ran in order. Up to the concurrency limit run
+    // at once, each on a worker of its own. If one fails the ones running are finished and
+    // the first error is returned.
+    pub async fn process_graph(&mut self, jobs: Vec<(usize, StepSpec)>) -> Result<(), Error> {
+        self.validate_graph()?;
+
+        let ids: Vec<usize> = jobs.iter().map(|(id, _)| *id).collect();
+        let waits: BTreeMap<usize, Vec<usize>> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let declared = self
+                    .reasoning_steps
+                    .iter()
+                    .find(|s| s.step_id == *id)
+                    .and_then(|s| s.depends_on.clone());
+                let waits = match declared {
+                    Some(depends_on) => depends_on
+                        .into_iter()
+                        .filter(|dependency| ids.contains(dependency))
+                        .collect(),
+                    None => ids[..i].last().copied().into_iter().collect(),
+                };
+                (*id, waits)
+            })
+            .collect();
+
+        let mut pending: VecDeque<(usize, StepSpec)> = jobs.into();
+        let mut done = HashSet::new();
+        let next_ready = |pending: &VecDeque<(usize, StepSpec)>, done: &HashSet<usize>| {
+            pending
+                .iter()
+                .position(|(id, _)| waits[id].iter().all(|dependency| done.contains(dependency)))
+        };
+
+        let factory = match &self.concurrency {
+            Some(concurrency) if concurrency.limit > 1 => {
+                Some((concurrency.limit, concurrency.factory.clone()))
+            }
+            _ => None,
+        };
+        let Some((limit, factory)) = factory else {
+            while let Some(position) = next_ready(&pending, &done) {
+                let Some((step_id, spec)) = pending.remove(position) else {
+                    break;
+                };
+                self.process_spec(step_id, &spec).await?;
+                done.insert(step_id);
+            }
+            return self.check_finished(&pending);
+        };
+
+        let mut idle = std::mem::take(&mut self.workers);
+        let mut built = idle.len();
+        let mut running = FuturesUnordered::new();
+        let mut failure = None;
+
+        loop {
+            while failure.is_none() && running.len() < limit {
+                let Some(position) = next_ready(&pending, &done) else {
+                    break;
+                };
+                let agent = match idle.pop() {
+                    Some(agent) => agent,
+                    None => match factory(built) {
+                        Ok(agent) => {
+                            built += 1;
+                            agent
+                        }
+                        Err(e) => {
+                            failure = Some(e);
+                            break;
+                        }
+                    },
+                };
+                let Some((step_id, spec)) = pending.remove(position) else {
+                    break;
+                };
+
+                let mut worker = self.worker(agent);
+                running.push(
+                    async move {
+                        let result = worker.process_spec(step_id, &spec).await;
+                        (worker, step_id, result)
+                    }
+                    .boxed_local(),
+                );
+            }
+
+            let Some((worker, step_id, result)) = running.next().await else {
+                break;
+            };
+            if let Some(processed) = worker.get_step(step_id)
+                && let Some(step) = self
+                    .reasoning_steps
+                    .iter_mut()
+                    .find(|s| s.step_id == step_id)
+            {
+                *step = processed;
+            }
+            idle.push(worker.agent);
+
+            match result {
+                Ok(()) => {
+                    done.insert(step_id);
+                }
+                Err(e) => {
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+
+        self.workers = idle;
+        match failure {
+            Some(e) => Err(e),
+            None => self.check_finished(&pending),
+        }
+    }
+
+    fn check_finished(&self, pending: &VecDeque<(usize, StepSpec)>) -> Result<(), Error> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!(
+            "Steps {} never became ready",
+            pending
+                .iter()
+                .map(|(id, _)| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+
+    // Reason through `step_id` as `spec` asks. `{previous}` in its prompt becomes what the
+    // steps it depends on concluded, or for a step without `depends_on` the latest conclusion
+    // before it.
+    pub async fn process_spec(&mut self, step_id: usize, spec: &StepSpec) -> Result<(), Error> {
+        let theme = self.dream_theme.clone().unwrap_or_default();
+        let prompt = spec.render(&theme, &self.previous_conclusions(step_id));
+        let samples = spec.samples.unwrap_or(1);
+        let reflect = spec.reflect.unwrap_or(0);
+
+        match &spec.kind {
+            StepKind::Open => {
+                self.process_step(step_id, &prompt, reflect).await?;
+            }
+            StepKind::Boolean => {
+                self.process_boolean_step(step_id, &prompt, samples).await?;
+            }
+            StepKind::Choice { options } => {
+                let options: Vec<&str> = options.iter().map(String::as_str).collect();
+                self.process_string_choice_step(step_id, &prompt, &options, samples)
+                    .await?;
+            }
+            StepKind::Numeric { min, max } => {
+                self.process_numeric_step(step_id, &prompt, *min, *max, samples)
+                    .await?;
+            }
+        }
+
+        // Keep the prompt with its placeholders, so a rerun picks up what the steps it builds
+        // on concluded since
+        if let Some(step) = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+        {
+            step.spec = Some(StepSpec {
+                description: step.desc.clone(),
+                depends_on: None,
+                ..spec.clone()
+            });
+        }
+        Ok(())
+    }
+
+    // Run a step again the way it was last run. Earlier runs stay in its history.
+    pub async fn rerun_step(&mut self, step_id: usize) -> Result<(), Error> {
+        let step = self.require_step(step_id)?;
+        let spec = step
+            .spec
+            .clone()
+            .filter(|_| step.has_run())
+            .ok_or(ChainOfThoughtError::NotProcessed { step_id })?;
+        self.process_spec(step_id, &spec).await
+    }
+
+    // Change what a step asks. Its reasoning stays as it is until the step is rerun, but a new
+    // description of an anchored step no longer matches the anchor, so the step goes stale.
+    pub fn edit_step(
+        &mut self,
+        step_id: usize,
+        description: Option<String>,
+        prompt: Option<String>,
+    ) -> Result<ReasoningStep, Error> {
+        let step = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
+
+        if let Some(description) = description {
+            if let Some(spec) = &mut step.spec {
+                spec.description = description.clone();
+            }
+            step.desc = description;
+        }
+        if let Some(prompt) = prompt {
+            step.spec
+                .get_or_insert_with(|| StepSpec::open(step.desc.clone()))
+                .prompt = Some(prompt);
+        }
+        step.stale = step.anchor_outdated();
+        step.history
+            .push(HistoryEntry::new(HistoryAction::Edited, step));
+
+        let step = step.clone();
+        self.emit(DreamEventKind::StepEdited {
+            step_id,
+            desc: step.desc.clone(),
+        });
+        Ok(step)
+    }
+
+    // Remove a step, unless other steps build on it or it's the branch taken
+    pub fn delete_step(&mut self, step_id: usize) -> Result<ReasoningStep, Error> {
+        if let Some(reason) = deletion_blocker(&self.reasoning_steps, step_id) {
+            return Err(ChainOfThoughtError::StepInUse { step_id, reason }.into());
+        }
+        let position = self
+            .reasoning_steps
+            .iter()
+            .position(|s| s.step_id == step_id)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
+        let step = self
+            .reasoning_steps
+            .remove(position)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
+
+        self.emit(DreamEventKind::StepDeleted { step_id });
+        Ok(step)
+    }
+
+    fn previous_conclusions(&self, step_id: usize) -> String {
+        let find = |id: usize| self.reasoning_steps.iter().find(|s| s.step_id == id);
+
+        let conclusions: Vec<String> = match find(step_id).and_then(|s| s.depends_on.as_ref()) {
+            Some(depends_on) => depends_on
+                .iter()
+                .filter_map(|id| find(*id)?.conclusion.as_ref().map(ToString::to_string))
+                .collect(),
+            None => self
+                .lineage(step_id)
+                .into_iter()
+                .find_map(|s| s.conclusion.as_ref())
+                .map(ToString::to_string)
+                .into_iter()
+                .collect(),
+        };
+
+        if conclusions.is_empty() {
+            "none".to_string()
+        } else {
+            conclusions.join("; ")
+        }
+    }
+
+    // Fork a concluded step into `count` alternative continuations: the agent proposes them,
+    // reasons through each as a branch of the step and scores them against each other, and the
+    // best-scored branch is chosen. Returns the IDs of the branches. If any of that fails the
+    // branches made so far are removed again, so a fan-out is never left half built.
+    pub async fn branch_step(
+        &mut self,
+        step_id: usize,
+        count: usize,
+        focus: Option<&str>,
+    ) -> Result<Vec<usize>, Error> {
+        let parent = self
+            .get_step(step_id)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
+        let conclusion = parent
+            .conclusion
+            .clone()
+            .ok_or(ChainOfThoughtError::NotProcessed { step_id })?;
+        let theme = self
+            .dream_theme
+            .clone()
+            .unwrap_or_else(|| "an ethereal dream".to_string());
+
+        let propose_prompt = format!(
+            "A chain of thought about {} reached this step:\n\nStep: {}\nConclusion: {}\n\n\
+            Propose exactly {} alternative continuations: distinct directions the reasoning could take next from this conclusion. \
+            Give each a short `description` and a `prompt` asking the one question it explores.{}\n\n\
+            Set `answer` to the list of continuations.",
+            theme,
+            parent.desc,
+            conclusion,
+            count,
+            focus
+                .map(|focus| format!("\n\nFocus the continuations on: {}", focus))
+                .unwrap_or_default()
+        );
+        let schema = structured::schema::<Vec<BranchProposal>>(|answer| {
+            answer["minItems"] = serde_json::json!(count);
+            answer["maxItems"] = serde_json::json!(count);
+        });
+        let proposals = self
+            .prompt_structured::<Vec<BranchProposal>>(None, &propose_prompt, &schema, |proposals| {
+                validate_proposals(proposals, count)
+            })
+            .await?
+            .answer;
+
+        let mut branch_ids = Vec::with_capacity(count);
+        let explored = self
+            .explore_branches(&parent, &conclusion, &theme, proposals, &mut branch_ids)
+            .await;
+        if let Err(e) = explored {
+            self.discard_steps(&branch_ids);
+            return Err(e);
+        }
+        Ok(branch_ids)
+    }
+
+    // Reason through each proposal as a branch of `parent`, adding its ID to `branch_ids` as
+    // soon as it exists, then score the branches and choose the best
+    async fn explore_branches(
+        &mut self,
+        parent: &ReasoningStep,
+        conclusion: &Conclusion,
+        theme: &str,
+        proposals: Vec<BranchProposal>,
+        branch_ids: &mut Vec<usize>,
+    ) -> Result<(), Error> {
+        for proposal in proposals {
+            let branch_id = self.add_branch(parent.step_id, proposal.description);
+            branch_ids.push(branch_id);
+            let prompt = format!(
+                "This continues from \"{}\", which concluded: {}\n\n{}",
+                parent.desc, conclusion, proposal.prompt
+            );
+            self.process_step(branch_id, &prompt, 0).await?;
+        }
+
+        let scores = self.score_branches(parent, branch_ids, theme).await?;
+        for (branch_id, score) in branch_ids.iter().zip(&scores) {
+            if let Some(step) = self
+                .reasoning_steps
+                .iter_mut()
+                .find(|s| s.step_id == *branch_id)
+            {
+                step.score = Some(*score);
+            }
+        }
+
+        // The first of equally scored branches wins
+        let best = scores
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, score)| **score)
+            .map(|(i, _)| branch_ids[i])
+            .unwrap_or(branch_ids[0]);
+        self.choose_branch(best)?;
+
+        Ok(())
+    }
+
+    // Remove steps nothing builds on yet, like the branches of a fan-out that failed
+    fn discard_steps(&mut self, step_ids: &[usize]) {
+        self.reasoning_steps
+            .retain(|s| !step_ids.contains(&s.step_id));
+        for step_id in step_ids {
+            self.run_calls.remove(step_id);
+            self.emit(DreamEventKind::StepDeleted { step_id: *step_id });
+        }
+    }
+
+    // Rate each branch of `parent` from 1 to MAX_BRANCH_SCORE, comparing them in one prompt
+    async fn score_branches(
+        &mut self,
+        parent: &ReasoningStep,
+        branch_ids: &[usize],
+        theme: &str,
+    ) -> Result<Vec<i32>, Error> {
+        let count = branch_ids.len();
+        let listing: String = branch_ids
+            .iter()
+            .filter_map(|id| self.reasoning_steps.iter().find(|s| s.step_id == *id))
+            .enumerate()
+            .map(|(i, step)| {
+                let outcome = match &step.conclusion {
+                    Some(conclusion) => conclusion.to_string(),
+                    None => step.reasoning.clone(),
+                };
+                format!("{}. {}: {}\n", i + 1, step.desc, outcome)
+            })
+            .collect();
+
+        let score_prompt = format!(
+            "Score each of these {} continuations of the step \"{}\" in a chain of thought about {}:\n\n{}\n\
+            Give each a score from 1 to {} for how soundly and usefully it advances the reasoning. \
+            Set `answer` to the scores, in the order the continuations are listed.",
+            count, parent.desc, theme, listing, MAX_BRANCH_SCORE
+        );
+        let schema = structured::schema::<Vec<i32>>(|answer| {
+            answer["minItems"] = serde_json::json!(count);
+            answer["maxItems"] = serde_json::json!(count);
+            answer["items"]["minimum"] = serde_json::json!(1);
+            answer["items"]["maximum"] = serde_json::json!(MAX_BRANCH_SCORE);
+        });
+
+        let scores = self
+            .prompt_structured::<Vec<i32>>(None, &score_prompt, &schema, |scores| {
+                if scores.len() != count {
+                    return Err(format!("expected {} scores, got {}", count, scores.len()));
+                }
+                match scores
+                    .iter()
+                    .find(|score| !(1..=MAX_BRANCH_SCORE).contains(*score))
+                {
+                    Some(score) => Err(format!("{} is outside 1..={}", score, MAX_BRANCH_SCORE)),
+                    None => Ok(()),
+                }
+            })
+            .await?
+            .answer;
+
+        Ok(scores)
+    }
+
+    // Take `step_id` as the path among the branches of its parent, over any earlier choice
+    pub fn choose_branch(&mut self, step_id: usize) -> Result<usize, Error> {
+        let parent = self
+            .get_step(step_id)
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?
+            .parent
+            .ok_or(ChainOfThoughtError::NotABranch { step_id })?;
+
+        for step in self
+            .reasoning_steps
+            .iter_mut()
+            .filter(|s| s.parent == Some(parent))
+        {
+            step.chosen = Some(step.step_id == step_id);
+        }
+        self.emit(DreamEventKind::BranchChosen { parent, step_id });
+
+        Ok(parent)
+    }
+
+    // With `reflect` rounds the agent then critiques each draft against the step and revises
+    // it, up to that many times or until a critique finds nothing to improve
+    pub async fn process_step(
+        &mut self,
+        step_id: usize,
+        prompt: &str,
+        reflect: usize,
+    ) -> Result<String, anyhow::Error> {
+        let desc = self.require_step(step_id)?.desc.clone();
+        self.begin_run(step_id);
+        let cot_prompt = format!(
+            "Please think through this step-by-step:\n\nStep: {}\nInstructions: {}\n\nFirst, break down the problem. Then analyze each part thoroughly. Finally, provide a conclusion.",
+            desc, prompt
+        );
+
+        let mut response = self.prompt_step(step_id, &cot_prompt).await?;
+        let mut revisions = Vec::new();
+
+        if reflect > 0 {
+            revisions.push(Revision::new(0, RevisionKind::Draft, response.clone()));
+
+            for round in 1..=reflect {
+                let critique_prompt = format!(
+                    "Critique this reasoning for the step below.\n\nStep: {}\nInstructions: {}\n\nReasoning:\n{}\n\nCheck it against the step and its instructions: point out factual errors, gaps, unsupported claims and whether the conclusion follows from the analysis. If there is nothing to improve, reply with exactly: {}",
+                    desc, prompt, response, NO_ISSUES
+                );
+                let critique = self.complete_step(step_id, &critique_prompt).await?;
+                let satisfied = critique
+                    .trim()
+                    .trim_end_matches('.')
+                    .eq_ignore_ascii_case(NO_ISSUES);
+                self.revise(
+                    step_id,
+                    &mut revisions,
+                    round,
+                    RevisionKind::Critique,
+                    critique.clone(),
+                );
+                if satisfied {
+                    break;
+                }
+
+                let revise_prompt = format!(
+                    "{}\n\nYour previous answer:\n{}\n\nA critique of it:\n{}\n\nWrite an improved answer that addresses the critique, ending with a line that starts with \"Conclusion:\".",
+                    cot_prompt, response, critique
+                );
+                response = self.complete_step(step_id, &revise_prompt).await?;
+                self.revise(
+                    step_id,
+                    &mut revisions,
+                    round,
+                    RevisionKind::Draft,
+                    response.clone(),
+                );
+            }
+        }
+
+        if let Some(step) = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+        {
+            step.reasoning = response.clone();
+            step.revisions = revisions;
+        }
+
+        let text = match conclusion::extract(&response) {
+            Some(text) => text,
+            None => self.summarize_reasoning(step_id, &desc, &response).await?,
+        };
+        if let Some(step) = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+        {
+            step.summary = (!text.is_empty()).then(|| conclusion::summarize(&text));
+            step.conclusion = (!text.is_empty()).then_some(Conclusion::Open { text });
+        }
+        let spec = self.run_spec(step_id, StepKind::Open, prompt, 1, reflect);
+        self.record_run(step_id, spec);
+        self.conclude(step_id);
+        Ok(response)
+    }
+
+    // When the reasoning has no labelled conclusion, ask the agent to state it in one sentence
+    async fn summarize_reasoning(
+        &mut self,
+        step_id: usize,
+        desc: &str,
+        reasoning: &str,
+    ) -> Result<String, anyhow::Error> {
+        let prompt = format!(
+            "Summarize the conclusion of the reasoning below in one sentence. Reply with only that sentence.\n\nStep: {}\n\nReasoning:\n{}",
+            desc, reasoning
+        );
+        let reply = self.call_model(step_id, &prompt).await?;
+        Ok(conclusion::extract(&reply).unwrap_or_else(|| conclusion::summarize(&reply)))
+    }
+
+    // The spec a run of `step_id` with these settings amounts to
+    fn run_spec(
+        &self,
+        step_id: usize,
+        kind: StepKind,
+        prompt: &str,
+        samples: usize,
+        reflect: usize,
+    ) -> StepSpec {
+        StepSpec {
+            description: self
+                .reasoning_steps
+                .iter()
+                .find(|s| s.step_id == step_id)
+                .map(|s| s.desc.clone())
+                .unwrap_or_default(),
+            kind,
+            prompt: Some(prompt.to_string()),
+            samples: Some(samples).filter(|samples| *samples > 1),
+            reflect: Some(reflect).filter(|reflect| *reflect > 0),
+            depends_on: None,
         }
     }
-}
 
-impl<M: Completion> ChainOfThought<M> {
-    pub fn new(agent: Agent<M>) -> Self {
-        Self {
-            reasoning_steps: VecDeque::new(),
-            agent,
-            current_step: 0,
-            dream_title: None,
-            dream_theme: None,
+    // Keep `spec` for reruns and log the run in the step's history
+    fn record_run(&mut self, step_id: usize, spec: StepSpec) {
+        let model = self.model.clone();
+        let calls = self.run_calls.remove(&step_id).unwrap_or_default();
+        if let Some(step) = self
+            .reasoning_steps
+            .iter_mut()
+            .find(|s| s.step_id == step_id)
+        {
+            step.spec = Some(spec);
+            step.calls = calls;
+            step.stale = step.anchor_outdated();
+            step.history.push(HistoryEntry {
+                model,
+                response: Some(step.reasoning.clone()),
+                conclusion: step.conclusion.clone(),
+                ..HistoryEntry::new(HistoryAction::Processed, step)
+            });
         }
     }
 
-    pub fn add_step(&mut self, desc: String) -> usize {
-        let step_id = self.current_step;
-        self.reasoning_steps
-            .push_back(ReasoningStep::new(step_id, desc));
-        self.current_step += 1;
-        step_id
+    fn revise(
+        &self,
+        step_id: usize,
+        revisions: &mut Vec<Revision>,
+        round: usize,
+        kind: RevisionKind,
+        text: String,
+    ) {
+        let revision = Revision::new(round, kind, text);
+        self.emit(DreamEventKind::StepRevised {
+            step_id,
+            revision: revision.clone(),
+        });
+        revisions.push(revision);
     }
 
-    pub async fn set_dream_context(&mut self, theme: &str) -> Result<(), Error> {
-        // Generate a creative title for this dream based on the theme
-        let title_prompt = format!(
-            "Generate a poetic, creative title for an AI's dream visualization about: {}. Respond with ONLY the title, no explanation.",
-            theme
-        );
+    // Ask for a schema-constrained JSON answer, re-prompting with the rejection reason until
+    // it parses and passes `validate` or the attempts run out. With a step it's one of the
+    // step's runs, streamed and recorded like its other replies; without one it's asked for
+    // the dream as a whole, like its plan or the branches of a step.
+    async fn prompt_structured<T: DeserializeOwned>(
+        &mut self,
+        step_id: Option<usize>,
+        prompt: &str,
+        schema: &str,
+        validate: impl Fn(&T) -> Result<(), String>,
+    ) -> Result<StructuredAnswer<T>, Error> {
+        let request = format!("{}\n\n{}", prompt, structured::instructions(schema));
+        let mut retry = request.clone();
+        let mut reason = String::new();
+        let mut response = String::new();
 
-        let title = self.agent.prompt(&title_prompt).await?;
-        self.dream_title = Some(title);
-        self.dream_theme = Some(theme.to_string());
+        for _ in 0..structured::MAX_ATTEMPTS {
+            response = match step_id {
+                Some(step_id) => self.prompt_step(step_id, &retry).await?,
+                None => self.prompt_agent(None, &retry).await?,
+            };
 
-        Ok(())
+            match structured::parse::<T>(&response)
+                .and_then(|answer| validate(&answer.answer).map(|_| answer))
+            {
+                Ok(answer) => return Ok(answer),
+                Err(e) => {
+                    reason = e;
+                    retry = format!(
+                        "{}\n\nYour previous reply was rejected ({}). Reply again with ONLY the JSON object.",
+                        request, reason
+                    );
+                }
+            }
+        }
+
+        Err(ChainOfThoughtError::ParseFailed(StructuredOutputError {
+            step_id,
+            attempts: structured::MAX_ATTEMPTS,
+            reason,
+            response,
+        })
+        .into())
     }
 
-    pub async fn process_step(
+    // Ask for `samples` structured answers to vote over. Samples that never produce a valid
+    // answer are left out of the vote; the step only fails if none do.
+    async fn sample_structured<T: DeserializeOwned>(
         &mut self,
         step_id: usize,
         prompt: &str,
-    ) -> Result<String, anyhow::Error> {
-        let cot_prompt = format!(
-            "Please think through this step-by-step:\n\nStep: {}\nInstructions: {}\n\nFirst, break down the problem. Then analyze each part thoroughly. Finally, provide a conclusion.",
-            self.reasoning_steps
-                .iter()
-                .find(|s| s.step_id == step_id)
-                .map(|s| &s.desc)
-                .unwrap_or(&String::from("Unknown")),
-            prompt
-        );
+        schema: &str,
+        validate: impl Fn(&T) -> Result<(), String>,
+        samples: usize,
+    ) -> Result<Vec<StructuredAnswer<T>>, Error> {
+        let mut answers = Vec::with_capacity(samples);
+        let mut last_error = None;
+
+        for _ in 0..samples.max(1) {
+            match self
+                .prompt_structured(Some(step_id), prompt, schema, &validate)
+                .await
+            {
+                Ok(answer) => answers.push(answer),
+                Err(e) if matches!(e.downcast_ref(), Some(ChainOfThoughtError::ParseFailed(_))) => {
+                    last_error = Some(e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-        let response = self.agent.prompt(&cot_prompt).await?;
+        match last_error {
+            Some(e) if answers.is_empty() => Err(e),
+            _ => Ok(answers),
+        }
+    }
 
+    fn record_answer<T>(
+        &mut self,
+        step_id: usize,
+        answer: &StructuredAnswer<T>,
+        conclusion: Conclusion,
+        votes: Option<Votes>,
+        spec: StepSpec,
+    ) {
         if let Some(step) = self
             .reasoning_steps
             .iter_mut()
             .find(|s| s.step_id == step_id)
         {
-            step.reasoning = response.clone();
-
-            if let Some(conclusion_idx) = response.to_lowercase().find("conclusion:") {
-                let conclusion = response[conclusion_idx..].trim().to_string();
-                step.conclusion = Some(conclusion);
-            }
+            step.reasoning = answer.reasoning.clone();
+            step.conclusion = Some(conclusion);
+            step.summary = None;
+            step.confidence = Some(answer.confidence);
+            step.votes = votes;
         }
-        Ok(response)
+        self.record_run(step_id, spec);
+        self.conclude(step_id);
     }
 
     pub async fn process_boolean_step(
         &mut self,
         step_id: usize,
         prompt: &str,
+        samples: usize,
     ) -> Result<bool, anyhow::Error> {
+        self.require_step(step_id)?;
+        self.begin_run(step_id);
         let boolean_prompt = format!(
-            "Please reason step-by-step to determine if the following statement is TRUE or FALSE: \n\n{}\n\nAfter your reasoning, conclude with ONLY the word 'TRUE' or 'FALSE'.",
+            "Please reason step-by-step to determine if the following statement is TRUE or FALSE: \n\n{}\n\nSet `answer` to true if the statement is TRUE and false if it is FALSE.",
             prompt
         );
+        let schema = structured::schema::<bool>(|_| {});
 
-        let response = self.agent.prompt(&boolean_prompt).await?;
+        let answers = self
+            .sample_structured::<bool>(step_id, &boolean_prompt, &schema, |_| Ok(()), samples)
+            .await?;
+        let (answer, votes) = majority_vote(answers, bool::to_string);
 
-        let result = response.trim().to_uppercase().ends_with("TRUE");
-
-        if let Some(step) = self
-            .reasoning_steps
-            .iter_mut()
-            .find(|s| s.step_id == step_id)
-        {
-            step.reasoning = response;
-            step.conclusion = Some(if result { "TRUE" } else { "FALSE" }.to_string());
-        }
+        let result = answer.answer;
+        let spec = self.run_spec(step_id, StepKind::Boolean, prompt, samples, 0);
+        self.record_answer(
+            step_id,
+            &answer,
+            Conclusion::Boolean { value: result },
+            votes,
+            spec,
+        );
 
         Ok(result)
     }
@@ -129,29 +1747,58 @@ impl<M: Completion> ChainOfThought<M> {
         step_id: usize,
         prompt: &str,
         options: &[&str],
+        samples: usize,
     ) -> Result<String, anyhow::Error> {
+        self.require_step(step_id)?;
+        self.begin_run(step_id);
         let option_str = options.join(", ");
 
         let choice_prompt = format!(
-            "Please reason step-by-step to select the BEST option from the following choices: {}\n\nQuestion: {}\n\nAfter your reasoning, conclude with ONLY one of the listed options.",
+            "Please reason step-by-step to select the BEST option from the following choices: {}\n\nQuestion: {}\n\nSet `answer` to exactly one of the listed options.",
             option_str, prompt
         );
-        let response = self.agent.prompt(&choice_prompt).await?;
+        let schema = structured::schema::<String>(|answer| {
+            answer["enum"] = serde_json::json!(options);
+        });
 
-        let selected_option = options
-            .iter()
-            .find(|&&opt| response.contains(opt))
-            .map(|&opt| opt.to_string())
-            .unwrap_or_else(|| "No clear selection".to_string());
+        let answers = self
+            .sample_structured::<String>(
+                step_id,
+                &choice_prompt,
+                &schema,
+                |selected| {
+                    if options.contains(&selected.as_str()) {
+                        Ok(())
+                    } else {
+                        Err(format!("'{}' is not one of the listed options", selected))
+                    }
+                },
+                samples,
+            )
+            .await?;
+        let (answer, votes) = majority_vote(answers, String::clone);
 
-        if let Some(step) = self
-            .reasoning_steps
-            .iter_mut()
-            .find(|s| s.step_id == step_id)
-        {
-            step.reasoning = response;
-            step.conclusion = Some(selected_option.clone());
-        }
+        let selected_option = answer.answer.clone();
+        let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        let spec = self.run_spec(
+            step_id,
+            StepKind::Choice {
+                options: options.clone(),
+            },
+            prompt,
+            samples,
+            0,
+        );
+        self.record_answer(
+            step_id,
+            &answer,
+            Conclusion::Choice {
+                options,
+                selected: selected_option.clone(),
+            },
+            votes,
+            spec,
+        );
 
         Ok(selected_option)
     }
@@ -162,65 +1809,112 @@ impl<M: Completion> ChainOfThought<M> {
         prompt: &str,
         min: i32,
         max: i32,
+        samples: usize,
     ) -> Result<i32, anyhow::Error> {
+        self.require_step(step_id)?;
+        self.begin_run(step_id);
         let numeric_prompt = format!(
-            "Please reason step-by-step to determine a numeric value between {} and {} (inclusive):\n\n{}\n\nAfter your reasoning, conclude with ONLY the final number.",
+            "Please reason step-by-step to determine a numeric value between {} and {} (inclusive):\n\n{}\n\nSet `answer` to the final integer.",
             min, max, prompt
         );
+        let schema = structured::schema::<i32>(|answer| {
+            answer["minimum"] = serde_json::json!(min);
+            answer["maximum"] = serde_json::json!(max);
+        });
 
-        let response = self.agent.prompt(&numeric_prompt).await?;
+        let answers = self
+            .sample_structured::<i32>(
+                step_id,
+                &numeric_prompt,
+                &schema,
+                |value| {
+                    if (min..=max).contains(value) {
+                        Ok(())
+                    } else {
+                        Err(format!("{} is outside {}..={}", value, min, max))
+                    }
+                },
+                samples,
+            )
+            .await?;
+        let (answer, votes) = median_vote(answers);
 
-        let number_str = response
-            .lines()
-            .last()
-            .unwrap_or("")
-            .trim()
-            .chars()
-            .filter(|c| c.is_digit(10) || *c == '-')
-            .collect::<String>();
+        let result = answer.answer;
+        let spec = self.run_spec(step_id, StepKind::Numeric { min, max }, prompt, samples, 0);
+        self.record_answer(
+            step_id,
+            &answer,
+            Conclusion::Numeric {
+                min,
+                max,
+                value: result,
+            },
+            votes,
+            spec,
+        );
 
-        let result = number_str.parse::<i32>().unwrap_or(0);
-        let bounded_result = result.max(min).min(max);
+        Ok(result)
+    }
 
-        if let Some(step) = self
+    pub async fn anchor_to_chain(
+        &mut self,
+        step_id: usize,
+        chain: &ChainClient,
+    ) -> Result<String, anyhow::Error> {
+        let step = self
             .reasoning_steps
             .iter_mut()
             .find(|s| s.step_id == step_id)
-        {
-            step.reasoning = response;
-            step.conclusion = Some(bounded_result.to_string());
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
+        if step.conclusion.is_none() {
+            return Err(ChainOfThoughtError::NotProcessed { step_id }.into());
+        }
+        if step.anchor_digest.is_some() && !step.anchor_outdated() {
+            return Err(ChainOfThoughtError::AlreadyAnchored { step_id }.into());
         }
 
-        Ok(bounded_result)
-    }
+        let digest = step.digest();
+        let receipt = chain
+            .anchor(digest)
+            .await
+            .map_err(|e| ChainOfThoughtError::Anchoring {
+                step_id,
+                reason: format!("{:#}", e),
+            })?;
 
-    pub async fn anchor_to_chain(&mut self, step_id: usize) -> Result<String, anyhow::Error> {
-        // For now, simulate blockchain anchoring with a random hash
-        // In a real implementation, this would use LazAI's data anchoring to Metis
-        let tx_hash = format!("0x{:016x}", rand::random::<u64>());
+        step.tx_hash = Some(receipt.tx_hash.clone());
+        step.block_number = receipt.block_number;
+        step.anchor_digest = Some(digest.to_string());
+        step.stale = false;
 
-        if let Some(step) = self
-            .reasoning_steps
-            .iter_mut()
-            .find(|s| s.step_id == step_id)
-        {
-            step.tx_hash = Some(tx_hash.clone());
-        }
+        self.emit(DreamEventKind::Anchored {
+            step_id,
+            tx_hash: receipt.tx_hash.clone(),
+            block_number: receipt.block_number,
+            digest: digest.to_string(),
+        });
 
-        Ok(tx_hash)
+        Ok(receipt.tx_hash)
     }
 
     pub fn get_steps(&self) -> Vec<ReasoningStep> {
         self.reasoning_steps.iter().cloned().collect()
     }
 
+    pub fn get_step(&self, step_id: usize) -> Option<ReasoningStep> {
+        self.reasoning_steps
+            .iter()
+            .find(|s| s.step_id == step_id)
+            .cloned()
+    }
+
     // AI-generated SVG for a specific reasoning step
     pub async fn generate_svg_for_step(&self, step_id: usize) -> Result<String, anyhow::Error> {
         let step = self
             .reasoning_steps
             .iter()
             .find(|s| s.step_id == step_id)
-            .ok_or_else(|| anyhow::anyhow!("Step not found"))?;
+            .ok_or(ChainOfThoughtError::StepNotFound { step_id })?;
 
         // Create a prompt asking the AI to generate SVG for this reasoning step
         let svg_prompt = format!(
@@ -235,7 +1929,7 @@ impl<M: Completion> ChainOfThought<M> {
         );
 
         // Get the AI to generate SVG
-        let response = self.agent.prompt(&svg_prompt).await?;
+        let response = self.prompt_agent(Some(step_id), &svg_prompt).await?;
 
         // Extract SVG code from response
         let svg_code = if let Some(start) = response.find("<svg") {
@@ -285,37 +1979,35 @@ impl<M: Completion> ChainOfThought<M> {
             title
         )?;
 
-        // Generate AI-created SVG for each reasoning step and position them in a circle
-        let center_x = width / 2;
-        let center_y = height / 2;
-
-        // Generate connections between steps
-        if self.reasoning_steps.len() > 1 {
-            for i in 0..self.reasoning_steps.len() - 1 {
-                let angle1 =
-                    2.0 * std::f64::consts::PI * (i as f64 / self.reasoning_steps.len() as f64);
-                let angle2 = 2.0
-                    * std::f64::consts::PI
-                    * ((i + 1) as f64 / self.reasoning_steps.len() as f64);
-
-                let radius = height as f64 * 0.35;
-
-                let x1 = center_x as f64 + radius * angle1.cos();
-                let y1 = center_y as f64 + radius * angle1.sin();
-                let x2 = center_x as f64 + radius * angle2.cos();
-                let y2 = center_y as f64 + radius * angle2.sin();
-
-                // Draw line connecting thoughts
-                writeln!(
-                    svg,
-                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="white" stroke-width="2" stroke-opacity="0.6" />"#,
-                    x1, y1, x2, y2
-                )?;
-            }
+        // Steps sit on a ring around a central node; once the dream has branched they're laid
+        // out as a tree instead, so alternative continuations fan out from their step
+        let branched = self.reasoning_steps.iter().any(|s| s.parent.is_some());
+        let (nodes, edges) = if branched {
+            self.tree_layout(width as f64, height as f64)
+        } else {
+            self.ring_layout(width as f64, height as f64)
+        };
+
+        // Draw the lines connecting thoughts; the path taken is gold, branches left behind dashed
+        for edge in &edges {
+            let style = match edge.chosen {
+                Some(true) => r#"stroke="gold" stroke-width="3" stroke-opacity="0.9""#,
+                Some(false) => {
+                    r#"stroke="white" stroke-width="1" stroke-opacity="0.3" stroke-dasharray="6 4""#
+                }
+                None => r#"stroke="white" stroke-width="2" stroke-opacity="0.6""#,
+            };
+            writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {} />"#,
+                edge.from.0, edge.from.1, edge.to.0, edge.to.1, style
+            )?;
         }
 
         // Add each AI-generated SVG for the steps
-        for (i, step) in self.reasoning_steps.iter().enumerate() {
+        for node in &nodes {
+            let step = &self.reasoning_steps[node.index];
+
             // Generate SVG for this step
             let step_svg = self.generate_svg_for_step(step.step_id).await?;
 
@@ -330,19 +2022,21 @@ impl<M: Completion> ChainOfThought<M> {
                 &step_svg
             };
 
-            // Position this SVG
-            let angle = 2.0 * std::f64::consts::PI * (i as f64 / self.reasoning_steps.len() as f64);
-            let radius = height as f64 * 0.35;
-
-            let x = center_x as f64 + radius * angle.cos();
-            let y = center_y as f64 + radius * angle.sin();
+            // Fade the steps of branches that weren't taken
+            let opacity = if self.off_path(step) {
+                r#" opacity="0.4""#
+            } else {
+                ""
+            };
 
             // Add a group with the AI-generated SVG, properly positioned and scaled
             writeln!(
                 svg,
-                r#"<g transform="translate({}, {}) scale(0.5)">{}</g>"#,
-                x - 150.0,
-                y - 150.0,
+                r#"<g transform="translate({}, {}) scale({})"{}>{}</g>"#,
+                node.x - node.extent,
+                node.y - node.extent,
+                node.scale,
+                opacity,
                 inner_content
             )?;
 
@@ -350,18 +2044,26 @@ impl<M: Completion> ChainOfThought<M> {
             writeln!(
                 svg,
                 r#"<text x="{}" y="{}" font-family="Arial" font-size="12" fill="white" text-anchor="middle">{}</text>"#,
-                x,
-                y - 155.0,
+                node.x,
+                node.y - node.extent - 5.0,
                 step.step_id + 1
             )?;
+
+            // Mark what the step concluded under its image
+            if let Some(conclusion) = &step.conclusion {
+                write_conclusion_badge(&mut svg, conclusion, node.x, node.y + node.extent + 10.0)?;
+            }
         }
 
         // Add a central node connecting all thoughts
-        writeln!(
-            svg,
-            r#"<circle cx="{}" cy="{}" r="20" fill="white" fill-opacity="0.9" stroke="gold" stroke-width="2" />"#,
-            center_x, center_y
-        )?;
+        if !branched {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="20" fill="white" fill-opacity="0.9" stroke="gold" stroke-width="2" />"#,
+                width / 2,
+                height / 2
+            )?;
+        }
 
         // Add current timestamp as metadata
         let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
@@ -378,26 +2080,479 @@ impl<M: Completion> ChainOfThought<M> {
         Ok(svg)
     }
 
-    pub async fn generate_dream_nft(&mut self) -> Result<(String, String), anyhow::Error> {
-        let _ = self.generate_svg_dream().await?;
+    // Steps evenly spaced on a ring, each linked to the next
+    fn ring_layout(&self, width: f64, height: f64) -> (Vec<SvgNode>, Vec<SvgEdge>) {
+        let count = self.reasoning_steps.len();
+        let radius = height * 0.35;
+        let position = |i: usize| {
+            let angle = 2.0 * std::f64::consts::PI * (i as f64 / count as f64);
+            (
+                width / 2.0 + radius * angle.cos(),
+                height / 2.0 + radius * angle.sin(),
+            )
+        };
+
+        let nodes = (0..count)
+            .map(|index| {
+                let (x, y) = position(index);
+                SvgNode {
+                    index,
+                    x,
+                    y,
+                    scale: 0.5,
+                    extent: 150.0,
+                }
+            })
+            .collect();
+        let edges = (1..count)
+            .map(|i| SvgEdge {
+                from: position(i - 1),
+                to: position(i),
+                chosen: None,
+            })
+            .collect();
+
+        (nodes, edges)
+    }
+
+    // Steps top to bottom as a tree: each step that doesn't branch from another leads to the
+    // next such step, and branches hang under the step they fork from
+    fn tree_layout(&self, width: f64, height: f64) -> (Vec<SvgNode>, Vec<SvgEdge>) {
+        let steps = &self.reasoning_steps;
+        let mut children = vec![Vec::new(); steps.len()];
+        for (i, step) in steps.iter().enumerate() {
+            if let Some(parent) = step
+                .parent
+                .and_then(|id| steps.iter().position(|s| s.step_id == id))
+            {
+                children[parent].push(i);
+            }
+        }
+        let roots: Vec<usize> = (0..steps.len())
+            .filter(|&i| steps[i].parent.is_none())
+            .collect();
+        for pair in roots.windows(2) {
+            children[pair[0]].push(pair[1]);
+        }
+
+        let mut depth = vec![0; steps.len()];
+        let mut column = vec![0.0; steps.len()];
+        let mut columns = 0;
+        if let Some(&root) = roots.first() {
+            place_subtree(root, 0, &children, &mut depth, &mut column, &mut columns);
+        }
+
+        let rows = depth.iter().max().map_or(1, |deepest| deepest + 1);
+        let column_width = (width - 100.0) / columns.max(1) as f64;
+        let row_height = (height - 200.0) / rows as f64;
+        let scale = (0.8 * column_width.min(row_height) / 300.0).min(0.5);
+        let position = |i: usize| {
+            (
+                50.0 + (column[i] + 0.5) * column_width,
+                100.0 + (depth[i] as f64 + 0.5) * row_height,
+            )
+        };
+
+        let nodes = (0..steps.len())
+            .map(|index| {
+                let (x, y) = position(index);
+                SvgNode {
+                    index,
+                    x,
+                    y,
+                    scale,
+                    extent: 150.0 * scale,
+                }
+            })
+            .collect();
+        let edges = children
+            .iter()
+            .enumerate()
+            .flat_map(|(i, children)| children.iter().map(move |&child| (i, child)))
+            .map(|(i, child)| SvgEdge {
+                from: position(i),
+                to: position(child),
+                chosen: steps[child].chosen,
+            })
+            .collect();
+
+        (nodes, edges)
+    }
+
+    // The SVG and metadata are expected to be content-addressed already (see `ipfs_cid`)
+    pub async fn generate_dream_nft(
+        &self,
+        chain: &ChainClient,
+        ipfs_cid: &str,
+        token_uri: &str,
+        recipient: Option<&str>,
+    ) -> Result<MintReceipt, anyhow::Error> {
+        println!("Dream visualization stored on IPFS with CID: {}", ipfs_cid);
 
-        // 2. For demo purposes, simulate IPFS storage and NFT minting
-        // In a real implementation, this would:
-        // - Store SVG to IPFS through Metis
-        // - Use returned CID to mint an NFT on Metis
-        let mock_ipfs_cid = format!("Qm{}", hex::encode(rand::random::<[u8; 16]>()));
-        let mock_nft_tx = format!("0x{}", hex::encode(rand::random::<[u8; 32]>()));
+        let receipt = chain.mint_dream(recipient, token_uri).await.map_err(|e| {
+            ChainOfThoughtError::Minting {
+                reason: format!("{:#}", e),
+            }
+        })?;
 
         println!(
-            "Dream visualization stored on IPFS with CID: {}",
-            mock_ipfs_cid
+            "NFT #{} minted to {} with transaction: {}",
+            receipt.token_id, receipt.owner, receipt.tx_hash
         );
-        println!("NFT minted with transaction: {}", mock_nft_tx);
 
-        // In a production implementation, here we would:
-        // 1. Use the Metis-IPFS integration to store the SVG
-        // 2. Call our NFT smart contract to mint with the IPFS CID
+        Ok(receipt)
+    }
+}
+
+// Where a step's image goes in the dream SVG: `extent` is the distance from its centre to the
+// corner of its image, which its number and conclusion badge sit just outside of
+struct SvgNode {
+    index: usize,
+    x: f64,
+    y: f64,
+    scale: f64,
+    extent: f64,
+}
+
+// A line between two steps, styled by whether it leads to the branch taken
+struct SvgEdge {
+    from: (f64, f64),
+    to: (f64, f64),
+    chosen: Option<bool>,
+}
+
+// Give each leaf of the subtree at `i` the next free column and centre every step over its
+// children, one row per level
+fn place_subtree(
+    i: usize,
+    level: usize,
+    children: &[Vec<usize>],
+    depth: &mut [usize],
+    column: &mut [f64],
+    columns: &mut usize,
+) {
+    depth[i] = level;
+    match (children[i].first(), children[i].last()) {
+        (Some(&first), Some(&last)) => {
+            for &child in &children[i] {
+                place_subtree(child, level + 1, children, depth, column, columns);
+            }
+            column[i] = (column[first] + column[last]) / 2.0;
+        }
+        _ => {
+            column[i] = *columns as f64;
+            *columns += 1;
+        }
+    }
+}
+
+// A small typed marker: a check or cross for booleans, the selected option for choices and a
+// gauge for numbers. Open-ended prose is left to the step's own image.
+fn write_conclusion_badge(
+    svg: &mut String,
+    conclusion: &Conclusion,
+    x: f64,
+    y: f64,
+) -> Result<(), fmt::Error> {
+    match conclusion {
+        Conclusion::Open { .. } => {}
+        Conclusion::Boolean { value } => {
+            let (color, mark) = if *value {
+                ("#2cb67d", "✓")
+            } else {
+                ("#e53170", "✗")
+            };
+            writeln!(
+                svg,
+                r##"<circle cx="{}" cy="{}" r="10" fill="{}" fill-opacity="0.8" /><text x="{}" y="{}" font-family="Arial" font-size="12" fill="white" text-anchor="middle">{}</text>"##,
+                x,
+                y,
+                color,
+                x,
+                y + 4.0,
+                mark
+            )?;
+        }
+        Conclusion::Choice { selected, .. } => {
+            writeln!(
+                svg,
+                r##"<text x="{}" y="{}" font-family="Arial" font-size="12" fill="#3da9fc" text-anchor="middle">{}</text>"##,
+                x,
+                y + 4.0,
+                escape_xml(selected)
+            )?;
+        }
+        Conclusion::Numeric { min, max, value } => {
+            let span = (*max - *min).max(1) as f64;
+            let filled = 80.0 * (*value - *min) as f64 / span;
+            writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="80" height="6" rx="3" fill="white" fill-opacity="0.2" /><rect x="{}" y="{}" width="{}" height="6" rx="3" fill="#ff8906" /><text x="{}" y="{}" font-family="Arial" font-size="10" fill="white" text-anchor="middle">{}</text>"##,
+                x - 40.0,
+                y - 3.0,
+                x - 40.0,
+                y - 3.0,
+                filled,
+                x,
+                y + 16.0,
+                value
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Conclude on the answer most samples gave, breaking ties by total confidence
+fn majority_vote<T: Clone>(
+    answers: Vec<StructuredAnswer<T>>,
+    key: impl Fn(&T) -> String,
+) -> (StructuredAnswer<T>, Option<Votes>) {
+    let mut tally: BTreeMap<String, (usize, f64)> = BTreeMap::new();
+    for answer in &answers {
+        let entry = tally.entry(key(&answer.answer)).or_default();
+        entry.0 += 1;
+        entry.1 += answer.confidence;
+    }
+    let winner = tally
+        .iter()
+        .max_by(|(_, a), (_, b)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+        .map(|(winner, _)| winner.clone())
+        .unwrap_or_default();
+
+    settle_vote(answers, key, &winner)
+}
+
+// Conclude on the median sample (the lower one for an even count), so outliers can't drag
+// the value the way a mean would
+fn median_vote(answers: Vec<StructuredAnswer<i32>>) -> (StructuredAnswer<i32>, Option<Votes>) {
+    let mut values: Vec<i32> = answers.iter().map(|a| a.answer).collect();
+    values.sort_unstable();
+    let median = values[(values.len() - 1) / 2];
+
+    settle_vote(answers, i32::to_string, &median.to_string())
+}
+
+// The concluded answer keeps the reasoning of the most confident sample that gave it and the
+// mean confidence of all of them. A single sample is no vote, so it records none.
+fn settle_vote<T: Clone>(
+    answers: Vec<StructuredAnswer<T>>,
+    key: impl Fn(&T) -> String,
+    winner: &str,
+) -> (StructuredAnswer<T>, Option<Votes>) {
+    let samples = answers.len();
+    let mut distribution = BTreeMap::new();
+    for answer in &answers {
+        *distribution.entry(key(&answer.answer)).or_insert(0) += 1;
+    }
+
+    let agreeing: Vec<StructuredAnswer<T>> = answers
+        .into_iter()
+        .filter(|a| key(&a.answer) == winner)
+        .collect();
+    let confidence = agreeing.iter().map(|a| a.confidence).sum::<f64>() / agreeing.len() as f64;
+    let mut answer = agreeing
+        .into_iter()
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+        .expect("the winning answer comes from at least one sample");
+    answer.confidence = confidence;
+
+    let votes = (samples > 1).then(|| Votes {
+        samples,
+        agreement: distribution.get(winner).copied().unwrap_or(0) as f64 / samples as f64,
+        distribution,
+    });
+
+    (answer, votes)
+}
+
+// A cycle in `graph` (each node to the nodes it depends on) as the path around it, if any
+pub fn find_cycle(graph: &BTreeMap<usize, Vec<usize>>) -> Option<Vec<usize>> {
+    // Nodes still on the path map to false, finished nodes to true
+    fn visit(
+        node: usize,
+        graph: &BTreeMap<usize, Vec<usize>>,
+        state: &mut BTreeMap<usize, bool>,
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<usize>> {
+        match state.get(&node) {
+            Some(true) => return None,
+            Some(false) => {
+                let start = path.iter().position(|n| *n == node).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(node);
+                return Some(cycle);
+            }
+            None => {}
+        }
+
+        state.insert(node, false);
+        path.push(node);
+        for dependency in graph.get(&node).into_iter().flatten() {
+            if let Some(cycle) = visit(*dependency, graph, state, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        state.insert(node, true);
+        None
+    }
+
+    let mut state = BTreeMap::new();
+    graph
+        .keys()
+        .find_map(|node| visit(*node, graph, &mut state, &mut Vec::new()))
+}
+
+fn validate_proposals(proposals: &[BranchProposal], count: usize) -> Result<(), String> {
+    if proposals.len() != count {
+        return Err(format!(
+            "expected {} continuations, got {}",
+            count,
+            proposals.len()
+        ));
+    }
+    for (i, proposal) in proposals.iter().enumerate() {
+        if proposal.description.trim().is_empty() || proposal.prompt.trim().is_empty() {
+            return Err(format!(
+                "continuation {} needs a description and a prompt",
+                i + 1
+            ));
+        }
+        if proposals[..i]
+            .iter()
+            .any(|p| p.description == proposal.description)
+        {
+            return Err(format!(
+                "continuation {} repeats '{}'",
+                i + 1,
+                proposal.description
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_plan(steps: &[StepSpec], count: usize) -> Result<(), String> {
+    if steps.len() != count {
+        return Err(format!("expected {} steps, got {}", count, steps.len()));
+    }
+    if let Some(i) = steps.iter().position(|step| step.prompt.is_none()) {
+        return Err(format!("step {} has no prompt", i + 1));
+    }
+    pipeline::validate_steps(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample<T>(answer: T, confidence: f64) -> StructuredAnswer<T> {
+        StructuredAnswer {
+            reasoning: format!("confidence {}", confidence),
+            answer,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn majority_vote_takes_the_most_common_answer() {
+        let answers = vec![sample(true, 0.9), sample(false, 0.6), sample(false, 0.5)];
+        let (answer, votes) = majority_vote(answers, bool::to_string);
+
+        assert!(!answer.answer);
+        // The most confident agreeing sample's reasoning, with their mean confidence
+        assert_eq!(answer.reasoning, "confidence 0.6");
+        assert_eq!(answer.confidence, 0.55);
+        let votes = votes.unwrap();
+        assert_eq!(votes.samples, 3);
+        assert_eq!(votes.distribution["false"], 2);
+        assert_eq!(votes.distribution["true"], 1);
+        assert_eq!(votes.agreement, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn majority_vote_breaks_a_tie_by_confidence() {
+        let answers = vec![
+            sample("red".to_string(), 0.4),
+            sample("blue".to_string(), 0.9),
+            sample("red".to_string(), 0.4),
+            sample("blue".to_string(), 0.8),
+        ];
+        let (answer, votes) = majority_vote(answers, Clone::clone);
+
+        assert_eq!(answer.answer, "blue");
+        assert_eq!(votes.unwrap().agreement, 0.5);
+    }
+
+    #[test]
+    fn majority_vote_tie_does_not_depend_on_sample_order() {
+        let answers = vec![sample(true, 0.5), sample(false, 0.5)];
+        let reversed: Vec<_> = answers.iter().rev().cloned().collect();
+
+        let (first, _) = majority_vote(answers, bool::to_string);
+        let (second, _) = majority_vote(reversed, bool::to_string);
+        assert_eq!(first.answer, second.answer);
+    }
+
+    #[test]
+    fn median_vote_takes_the_lower_middle_value() {
+        let answers = vec![
+            sample(9, 0.5),
+            sample(1, 0.5),
+            sample(7, 0.5),
+            sample(3, 0.5),
+        ];
+        let (answer, votes) = median_vote(answers);
+
+        assert_eq!(answer.answer, 3);
+        assert_eq!(votes.unwrap().agreement, 0.25);
+    }
+
+    #[test]
+    fn median_vote_ignores_an_outlier() {
+        let answers = vec![sample(5, 0.5), sample(1000, 0.9), sample(6, 0.7)];
+        let (answer, _) = median_vote(answers);
+        assert_eq!(answer.answer, 6);
+    }
+
+    #[test]
+    fn a_single_sample_records_no_vote() {
+        let (answer, votes) = majority_vote(vec![sample(true, 0.7)], bool::to_string);
+        assert!(answer.answer);
+        assert_eq!(answer.confidence, 0.7);
+        assert!(votes.is_none());
+    }
+
+    #[test]
+    fn find_cycle_returns_the_path_around_it() {
+        let graph = BTreeMap::from([(0, vec![]), (1, vec![0, 3]), (2, vec![1]), (3, vec![2])]);
+        assert_eq!(find_cycle(&graph), Some(vec![1, 3, 2, 1]));
+
+        let graph = BTreeMap::from([(0, vec![0])]);
+        assert_eq!(find_cycle(&graph), Some(vec![0, 0]));
+    }
+
+    #[test]
+    fn find_cycle_accepts_a_diamond() {
+        let graph = BTreeMap::from([(0, vec![]), (1, vec![0]), (2, vec![0]), (3, vec![1, 2])]);
+        assert_eq!(find_cycle(&graph), None);
+    }
+
+    #[test]
+    fn a_run_that_concluded_nothing_still_counts_as_run() {
+        let mut step = ReasoningStep::new(0, "Aroma".to_string());
+        assert!(!step.has_run());
 
-        Ok((mock_ipfs_cid, mock_nft_tx))
+        let entry = HistoryEntry::new(HistoryAction::Processed, &step);
+        step.history.push(entry);
+        assert_eq!(step.conclusion, None);
+        assert!(step.has_run());
     }
 }
@@ -0,0 +1,243 @@
+// Finding the conclusion in the free-form reply of an open-ended step
+
+// Labels that head a conclusion section, e.g. "Conclusion:", "**Conclusion**" or
+// "## Final answer". Longer labels come first so "final conclusion" wins over "conclusion".
+const HEADING_LABELS: &[&str] = &[
+    "final conclusion",
+    "final answer",
+    "final thoughts",
+    "conclusion",
+    "summary",
+    "bottom line",
+    "tl;dr",
+];
+
+// Phrases that open a concluding sentence, e.g. "In conclusion, ..."
+const INLINE_LABELS: &[&str] = &["in conclusion", "to conclude", "in summary", "to summarize"];
+
+// Longest summary kept, in characters
+pub const MAX_SUMMARY_CHARS: usize = 160;
+
+// The conclusion of `response`, without its label or markdown. The last labelled conclusion
+// wins. A heading's section runs to the first blank line or the next heading, markdown or
+// bold, so a numbered summary under a "Summary" heading is kept whole but closing remarks and
+// "**Next steps**" after it are left out. None when the reply has no recognisable conclusion.
+pub fn extract(response: &str) -> Option<String> {
+    let lines: Vec<&str> = response.lines().collect();
+
+    let (index, rest, heading) = lines
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, line)| match_label(line).map(|(rest, heading)| (i, rest, heading)))?;
+
+    let mut parts = vec![rest];
+    if heading {
+        parts.extend(section(&lines[index + 1..]));
+    }
+
+    let text = clean(&parts.join(" "));
+    (!text.is_empty()).then_some(text)
+}
+
+// The lines of the section under a heading. Blank lines before it starts are skipped, and a
+// list may have blank lines between its items.
+fn section<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let mut section: Vec<&str> = Vec::new();
+    let mut in_list = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if is_heading(line) {
+            break;
+        }
+        if line.trim().is_empty() {
+            let list_continues = lines[i + 1..]
+                .iter()
+                .find(|line| !line.trim().is_empty())
+                .is_some_and(|next| is_list_item(next));
+            if section.is_empty() || (in_list && list_continues) {
+                continue;
+            }
+            break;
+        }
+        in_list = is_list_item(line);
+        section.push(strip_markers(line));
+    }
+    section
+}
+
+// "## Next steps" or a line that is nothing but bold text, like "**Next steps**"
+fn is_heading(line: &str) -> bool {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return true;
+    }
+    ["**", "__"].iter().any(|marker| {
+        line.strip_prefix(marker)
+            .and_then(|line| line.strip_suffix(marker))
+            .is_some_and(|inner| !inner.trim().is_empty() && !inner.contains(marker))
+    })
+}
+
+fn is_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    let number = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    (number.len() < line.len() && number.starts_with(['.', ')']))
+        || ["- ", "* ", "+ "]
+            .iter()
+            .any(|bullet| line.starts_with(bullet))
+}
+
+// The first sentence of `text`, cut to MAX_SUMMARY_CHARS at a word boundary
+pub fn summarize(text: &str) -> String {
+    let text = clean(text);
+    let sentence = text
+        .char_indices()
+        .find(|(i, c)| {
+            matches!(c, '.' | '!' | '?')
+                && text[i + c.len_utf8()..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        })
+        .map_or(text.as_str(), |(i, c)| &text[..i + c.len_utf8()]);
+
+    if sentence.chars().count() <= MAX_SUMMARY_CHARS {
+        return sentence.to_string();
+    }
+    let cut: String = sentence.chars().take(MAX_SUMMARY_CHARS - 1).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!("{}…", cut.trim_end_matches([',', ';', ':']))
+}
+
+// If `line` starts with a conclusion label, the text after it and whether the label is a
+// heading whose section continues on the following lines
+fn match_label(line: &str) -> Option<(&str, bool)> {
+    let stripped = strip_markers(line);
+    let lower = stripped.to_lowercase();
+    let after = |label: &str| {
+        lower
+            .starts_with(label)
+            .then(|| stripped.get(label.len()..))
+            .flatten()
+    };
+
+    // "Conclusion:" or "**Conclusion**", but not "Conclusions drawn" or "Summary of"
+    let heading = HEADING_LABELS
+        .iter()
+        .filter_map(|label| after(label))
+        .find(|rest| {
+            rest.trim().is_empty() || rest.starts_with([':', '*', '_', '#', '-', '–', '—'])
+        });
+    if let Some(rest) = heading {
+        return Some((rest, true));
+    }
+    INLINE_LABELS
+        .iter()
+        .filter_map(|label| after(label))
+        .find(|rest| rest.starts_with([',', ':']))
+        .map(|rest| (rest, false))
+}
+
+// Leading indentation, list numbers and bullets, heading hashes and emphasis
+fn strip_markers(line: &str) -> &str {
+    let line = line.trim_start();
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let line = if digits > 0 && line[digits..].starts_with(['.', ')']) {
+        &line[digits + 1..]
+    } else {
+        line
+    };
+    line.trim_start_matches(['-', '*', '+', '#', '_', '>', ' '])
+}
+
+// Drop leftover label punctuation and emphasis, collapse whitespace and capitalize
+fn clean(text: &str) -> String {
+    let text = text.replace("**", "").replace("__", "");
+    let text = text
+        .trim()
+        .trim_start_matches([':', ',', '-', '–', '—', '*', '_', '#', ' '])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bold_heading_ends_at_the_next_bold_heading() {
+        let response = "Some analysis.\n\n**Conclusion**\nRivers carry memory.\n\n**Next steps**\nMap the tributaries.";
+        assert_eq!(extract(response).as_deref(), Some("Rivers carry memory."));
+    }
+
+    #[test]
+    fn markdown_heading_skips_the_blank_line_after_it_and_stops_at_the_next_one() {
+        let response = "## Analysis\nLots of thought.\n\n## Conclusion\n\nThe dream is a map.\nIt has no edges.\n\nThanks for reading!";
+        assert_eq!(
+            extract(response).as_deref(),
+            Some("The dream is a map. It has no edges.")
+        );
+    }
+
+    #[test]
+    fn labelled_line_keeps_its_text() {
+        let response = "Reasoning here.\nConclusion: **bridges** hold the memory of rivers.";
+        assert_eq!(
+            extract(response).as_deref(),
+            Some("Bridges hold the memory of rivers.")
+        );
+    }
+
+    #[test]
+    fn inline_conclusion_takes_only_its_line() {
+        let response = "Thinking a lot.\nIn conclusion, the dream is a map.\nThat said, maps lie.";
+        assert_eq!(extract(response).as_deref(), Some("The dream is a map."));
+    }
+
+    #[test]
+    fn numbered_summary_is_kept_whole() {
+        let response = "Analysis.\n\n### Summary\n1. Rivers carry memory.\n\n2. Bridges hold it.\n\nLet me know if you want more.";
+        assert_eq!(
+            extract(response).as_deref(),
+            Some("Rivers carry memory. Bridges hold it.")
+        );
+    }
+
+    #[test]
+    fn last_conclusion_wins() {
+        let response =
+            "Conclusion: a first guess.\n\nOn reflection...\n\nFinal answer: the second one.";
+        assert_eq!(extract(response).as_deref(), Some("The second one."));
+    }
+
+    #[test]
+    fn words_that_only_start_like_a_label_are_not_one() {
+        assert_eq!(extract("Conclusions drawn from rivers are slow."), None);
+        assert_eq!(extract("Summary of the chain so far: it flows."), None);
+    }
+
+    #[test]
+    fn no_label_falls_back_to_the_first_sentence() {
+        let response = "Just rambling without any label at all. More rambling follows.";
+        assert_eq!(extract(response), None);
+        assert_eq!(
+            summarize(response),
+            "Just rambling without any label at all."
+        );
+    }
+
+    #[test]
+    fn long_summaries_are_cut_at_a_word() {
+        let summary = summarize(&"word ".repeat(100));
+        assert!(summary.chars().count() <= MAX_SUMMARY_CHARS);
+        assert!(summary.ends_with("word…"));
+    }
+}
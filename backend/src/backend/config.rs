@@ -0,0 +1,366 @@
+use crate::backend::chain::parse_address;
+use crate::backend::chain_of_thought::{ContextWindow, DEFAULT_CONTEXT_CHARS};
+use crate::backend::pipeline::{self, Pipelines};
+use anyhow::Result;
+use reqwest::Url;
+use serde::Deserialize;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+
+// Read from the working directory when DREAM_CONFIG isn't set
+const DEFAULT_CONFIG_PATH: &str = "dreamcatcher.toml";
+
+const DEFAULT_PREAMBLE: &str = "You are an artistic AI with exceptional chain of thought reasoning capabilities. You carefully analyze problems step by step and visualize your thinking process as abstract dream-like images.";
+
+// Server settings, read from a TOML file and then overridden by environment variables.
+// Every field has a default, so both the file and each of its sections are optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub model: ModelConfig,
+    pub chain: ChainSettings,
+    pub storage: StorageConfig,
+    pub ipfs: IpfsSettings,
+    pub continuous: ContinuousDefaults,
+    // Named step pipelines, added to (or replacing) the built-in ones
+    pub pipelines: Pipelines,
+    // The file the settings were read from, if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    // Address the HTTP server listens on
+    pub bind: String,
+    // Address clients and marketplaces reach this server at, used in token URIs and NFT metadata
+    pub base_url: String,
+    // Origins allowed to call the API from a browser; "*" allows any
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:8080".to_string(),
+            base_url: "http://127.0.0.1:8080".to_string(),
+            // The Vite dev server of dreamcatcher-frontend
+            cors_origins: vec![
+                "http://localhost:5173".to_string(),
+                "http://127.0.0.1:5173".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    // OpenAI model used when DREAM_PROVIDER is openai
+    pub name: String,
+    pub preamble: String,
+    // How many independent steps of a dream may be processed at once
+    pub concurrency: usize,
+    // Earlier conclusions fed into each step's prompt; 0 (the default) leaves prompts as they are
+    pub context_steps: usize,
+    // Character budget for those conclusions; older ones are cut first
+    pub context_chars: usize,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            name: "gpt-4o-mini".to_string(),
+            preamble: DEFAULT_PREAMBLE.to_string(),
+            concurrency: 4,
+            context_steps: 0,
+            context_chars: DEFAULT_CONTEXT_CHARS,
+        }
+    }
+}
+
+impl ModelConfig {
+    // None while context is off
+    pub fn context_window(&self) -> Option<ContextWindow> {
+        (self.context_steps > 0).then_some(ContextWindow {
+            max_steps: self.context_steps,
+            max_chars: self.context_chars,
+        })
+    }
+}
+
+// The chain's private key is deliberately not part of the file; it only comes from
+// CHAIN_PRIVATE_KEY
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainSettings {
+    pub rpc_url: Option<String>,
+    pub anchor_contract: Option<String>,
+    pub nft_contract: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    // SQLite file holding dreams, steps, SVGs and NFT results, created on first run
+    pub db_path: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            db_path: "dreams.db".to_string(),
+        }
+    }
+}
+
+// Minted SVGs and metadata are always served from /ipfs; a Kubo node also pins them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IpfsSettings {
+    pub api_url: Option<String>,
+}
+
+// Used for continuous dreaming requests that leave these out
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContinuousDefaults {
+    pub interval_secs: u64,
+    pub max_iterations: Option<usize>,
+    pub evolve_theme: bool,
+}
+
+impl Default for ContinuousDefaults {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            max_iterations: None,
+            evolve_theme: false,
+        }
+    }
+}
+
+impl Config {
+    // Read DREAM_CONFIG (or ./dreamcatcher.toml if it exists), apply environment overrides and
+    // check the result, so a bad setting stops the server at startup rather than on first use
+    pub fn load() -> Result<Self> {
+        let path = match std::env::var("DREAM_CONFIG") {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from(DEFAULT_CONFIG_PATH)).filter(|path| path.exists()),
+        };
+
+        let mut config = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(&path).map_err(|e| {
+                    anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e)
+                })?;
+                let mut config: Config = toml::from_str(&contents).map_err(|e| {
+                    anyhow::anyhow!("Invalid config file {}: {}", path.display(), e)
+                })?;
+                config.path = Some(path);
+                config
+            }
+            None => Config::default(),
+        };
+
+        config.pipelines = pipeline::builtin()
+            .into_iter()
+            .chain(config.pipelines)
+            .collect();
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        override_string("DREAM_BIND_ADDRESS", &mut self.server.bind);
+        override_string("DREAM_BASE_URL", &mut self.server.base_url);
+        if let Ok(origins) = std::env::var("DREAM_CORS_ORIGINS") {
+            self.server.cors_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        override_string("DREAM_MODEL", &mut self.model.name);
+        override_string("DREAM_PREAMBLE", &mut self.model.preamble);
+        if let Ok(concurrency) = std::env::var("DREAM_MODEL_CONCURRENCY") {
+            self.model.concurrency = concurrency.parse().map_err(|_| {
+                anyhow::anyhow!("DREAM_MODEL_CONCURRENCY must be a positive integer")
+            })?;
+        }
+        if let Ok(steps) = std::env::var("DREAM_CONTEXT_STEPS") {
+            self.model.context_steps = steps.parse().map_err(|_| {
+                anyhow::anyhow!("DREAM_CONTEXT_STEPS must be a non-negative integer")
+            })?;
+        }
+        if let Ok(chars) = std::env::var("DREAM_CONTEXT_CHARS") {
+            self.model.context_chars = chars.parse().map_err(|_| {
+                anyhow::anyhow!("DREAM_CONTEXT_CHARS must be a non-negative integer")
+            })?;
+        }
+
+        override_option("CHAIN_RPC_URL", &mut self.chain.rpc_url);
+        override_option("ANCHOR_CONTRACT_ADDRESS", &mut self.chain.anchor_contract);
+        override_option("DREAM_NFT_CONTRACT_ADDRESS", &mut self.chain.nft_contract);
+
+        override_string("DREAM_DB_PATH", &mut self.storage.db_path);
+        override_option("IPFS_API_URL", &mut self.ipfs.api_url);
+
+        if let Ok(secs) = std::env::var("DREAM_CONTINUOUS_INTERVAL_SECS") {
+            self.continuous.interval_secs = secs.parse().map_err(|_| {
+                anyhow::anyhow!("DREAM_CONTINUOUS_INTERVAL_SECS must be a positive integer")
+            })?;
+        }
+        if let Ok(iterations) = std::env::var("DREAM_CONTINUOUS_MAX_ITERATIONS") {
+            self.continuous.max_iterations = match iterations.trim() {
+                "" => None,
+                iterations => Some(iterations.parse().map_err(|_| {
+                    anyhow::anyhow!("DREAM_CONTINUOUS_MAX_ITERATIONS must be a positive integer")
+                })?),
+            };
+        }
+        if let Ok(evolve) = std::env::var("DREAM_CONTINUOUS_EVOLVE_THEME") {
+            self.continuous.evolve_theme = evolve.parse().map_err(|_| {
+                anyhow::anyhow!("DREAM_CONTINUOUS_EVOLVE_THEME must be true or false")
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // Check every setting, normalizing URLs and origins along the way
+    fn validate(&mut self) -> Result<()> {
+        let server = &mut self.server;
+
+        server
+            .bind
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "server.bind (DREAM_BIND_ADDRESS) must be a host:port address, got '{}'",
+                    server.bind
+                )
+            })?;
+
+        server.base_url = http_url(&server.base_url)
+            .filter(|url| url.query().is_none() && url.fragment().is_none())
+            .map(|url| url.as_str().trim_end_matches('/').to_string())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "server.base_url (DREAM_BASE_URL) must be an http(s) URL like http://127.0.0.1:8080, got '{}'",
+                    server.base_url
+                )
+            })?;
+
+        server.cors_origins = server
+            .cors_origins
+            .iter()
+            .map(|origin| {
+                if origin == "*" {
+                    return Ok(origin.clone());
+                }
+                http_url(origin)
+                    .filter(|url| url.path() == "/" && url.query().is_none())
+                    .map(|url| url.origin().ascii_serialization())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "server.cors_origins (DREAM_CORS_ORIGINS) entries must be \"*\" or an origin like http://localhost:5173, got '{}'",
+                            origin
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?;
+
+        if self.model.name.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "model.name (DREAM_MODEL) must not be empty"
+            ));
+        }
+        if self.model.preamble.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "model.preamble (DREAM_PREAMBLE) must not be empty"
+            ));
+        }
+        if self.model.concurrency == 0 {
+            return Err(anyhow::anyhow!(
+                "model.concurrency (DREAM_MODEL_CONCURRENCY) must be at least 1"
+            ));
+        }
+
+        if let Some(rpc_url) = &self.chain.rpc_url {
+            http_url(rpc_url).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "chain.rpc_url (CHAIN_RPC_URL) must be an http(s) URL, got '{}'",
+                    rpc_url
+                )
+            })?;
+        }
+        if let Some(address) = &self.chain.anchor_contract {
+            parse_address(address, "chain.anchor_contract (ANCHOR_CONTRACT_ADDRESS)")?;
+        }
+        if let Some(address) = &self.chain.nft_contract {
+            parse_address(address, "chain.nft_contract (DREAM_NFT_CONTRACT_ADDRESS)")?;
+        }
+
+        if self.storage.db_path.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "storage.db_path (DREAM_DB_PATH) must not be empty"
+            ));
+        }
+        if let Some(api_url) = &mut self.ipfs.api_url {
+            *api_url = http_url(api_url)
+                .map(|url| url.as_str().trim_end_matches('/').to_string())
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "ipfs.api_url (IPFS_API_URL) must be an http(s) URL like http://127.0.0.1:5001, got '{}'",
+                        api_url
+                    )
+                })?;
+        }
+
+        if self.continuous.interval_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "continuous.interval_secs (DREAM_CONTINUOUS_INTERVAL_SECS) must be at least 1"
+            ));
+        }
+        if self.continuous.max_iterations == Some(0) {
+            return Err(anyhow::anyhow!(
+                "continuous.max_iterations (DREAM_CONTINUOUS_MAX_ITERATIONS) must be at least 1"
+            ));
+        }
+
+        for (name, steps) in &self.pipelines {
+            pipeline::validate_steps(steps)
+                .map_err(|e| anyhow::anyhow!("pipelines.{}: {}", name, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn override_string(var: &str, value: &mut String) {
+    if let Ok(env) = std::env::var(var) {
+        *value = env;
+    }
+}
+
+fn override_option(var: &str, value: &mut Option<String>) {
+    if let Ok(env) = std::env::var(var) {
+        *value = Some(env).filter(|env| !env.is_empty());
+    }
+}
+
+fn http_url(url: &str) -> Option<Url> {
+    Url::parse(url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
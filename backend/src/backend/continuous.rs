@@ -0,0 +1,195 @@
+use crate::backend::pipeline::StepPlan;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant, sleep_until};
+
+// How a continuous dreaming session builds each of its dreams
+#[derive(Debug, Clone)]
+pub struct ContinuousSettings {
+    pub theme: String,
+    pub steps: StepPlan,
+    pub auto_mint: bool,
+    pub interval: Duration,
+    pub max_iterations: Option<usize>,
+    pub evolve_theme: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    Running,
+    Paused,
+    // Stopped through the API
+    Stopped,
+    // Reached max_iterations
+    Finished,
+}
+
+impl SessionState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionState::Running => "running",
+            SessionState::Paused => "paused",
+            SessionState::Stopped => "stopped",
+            SessionState::Finished => "finished",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStatus {
+    pub session_id: String,
+    pub state: SessionState,
+    pub theme: String,
+    pub interval_secs: u64,
+    pub max_iterations: Option<usize>,
+    pub dream_ids: Vec<String>,
+    pub next_run_at: Option<String>,
+    pub last_error: Option<String>,
+    // When the session was stopped or finished
+    #[serde(skip)]
+    pub ended_at: Option<Instant>,
+}
+
+// A continuous dreaming session as seen by both its task and the API. The API asks for state
+// changes through `control`; the task reports progress in `status`.
+#[derive(Clone)]
+pub struct ContinuousSession {
+    status: Arc<Mutex<SessionStatus>>,
+    control: Arc<watch::Sender<SessionState>>,
+}
+
+impl ContinuousSession {
+    pub fn new(session_id: &str, first_dream_id: &str, settings: &ContinuousSettings) -> Self {
+        let status = SessionStatus {
+            session_id: session_id.to_string(),
+            state: SessionState::Running,
+            theme: settings.theme.clone(),
+            interval_secs: settings.interval.as_secs(),
+            max_iterations: settings.max_iterations,
+            dream_ids: vec![first_dream_id.to_string()],
+            next_run_at: None,
+            last_error: None,
+            ended_at: None,
+        };
+
+        Self {
+            status: Arc::new(Mutex::new(status)),
+            control: Arc::new(watch::Sender::new(SessionState::Running)),
+        }
+    }
+
+    // Status is only ever held for a read or a small update, so a poisoned lock still has
+    // consistent contents
+    fn lock(&self) -> MutexGuard<'_, SessionStatus> {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.lock().clone()
+    }
+
+    pub fn update(&self, update: impl FnOnce(&mut SessionStatus)) {
+        update(&mut self.lock());
+    }
+
+    // Whether the session stopped or finished at least `age` ago
+    pub fn ended_before(&self, age: Duration) -> bool {
+        self.lock()
+            .ended_at
+            .is_some_and(|ended_at| ended_at.elapsed() >= age)
+    }
+
+    pub fn has_dream(&self, dream_id: &str) -> bool {
+        self.lock().dream_ids.iter().any(|id| id == dream_id)
+    }
+
+    // Pause, resume or stop the session on behalf of the API
+    pub fn request(&self, state: SessionState) -> Result<SessionStatus, String> {
+        let mut status = self.lock();
+
+        let allowed = match (status.state, state) {
+            (SessionState::Stopped | SessionState::Finished, _) => false,
+            (_, SessionState::Stopped) => true,
+            (SessionState::Running, SessionState::Paused) => true,
+            (SessionState::Paused, SessionState::Running) => true,
+            _ => false,
+        };
+        if !allowed {
+            return Err(format!(
+                "Cannot {} a {} session",
+                action(state),
+                status.state.name()
+            ));
+        }
+
+        status.state = state;
+        if state != SessionState::Running {
+            status.next_run_at = None;
+        }
+        if state == SessionState::Stopped {
+            status.ended_at = Some(Instant::now());
+        }
+        self.control.send_replace(state);
+
+        Ok(status.clone())
+    }
+
+    // Mark the session as done on its own, unless it was already stopped
+    pub fn finish(&self) {
+        let mut status = self.lock();
+        if status.state != SessionState::Stopped {
+            status.state = SessionState::Finished;
+        }
+        status.next_run_at = None;
+        status.ended_at.get_or_insert_with(Instant::now);
+    }
+
+    // Wait out `interval` while the session runs, holding the clock while it is paused.
+    // Returns false once the session has been stopped.
+    pub async fn wait(&self, interval: Duration) -> bool {
+        let mut control = self.control.subscribe();
+        let mut remaining = interval;
+
+        loop {
+            let state = *control.borrow_and_update();
+            match state {
+                SessionState::Running => {
+                    let deadline = Instant::now() + remaining;
+                    self.update(|s| s.next_run_at = Some(timestamp_in(remaining)));
+
+                    tokio::select! {
+                        _ = sleep_until(deadline) => return true,
+                        changed = control.changed() => {
+                            if changed.is_err() {
+                                return false;
+                            }
+                            remaining = deadline.saturating_duration_since(Instant::now());
+                        }
+                    }
+                }
+                SessionState::Paused => {
+                    if control.changed().await.is_err() {
+                        return false;
+                    }
+                }
+                SessionState::Stopped | SessionState::Finished => return false,
+            }
+        }
+    }
+}
+
+fn action(state: SessionState) -> &'static str {
+    match state {
+        SessionState::Running => "resume",
+        SessionState::Paused => "pause",
+        SessionState::Stopped | SessionState::Finished => "stop",
+    }
+}
+
+fn timestamp_in(delay: Duration) -> String {
+    let delay = chrono::Duration::from_std(delay).unwrap_or_default();
+    (Utc::now() + delay).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,149 @@
+use crate::backend::chain_of_thought::{Conclusion, Revision};
+use crate::backend::pipeline::StepSpec;
+use crate::backend::storage::NftRecord;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+// Events buffered per subscriber before slow SSE clients start missing some
+const EVENT_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum DreamEventKind {
+    DreamCreated {
+        title: Option<String>,
+        theme: Option<String>,
+    },
+    // The agent planned the dream's steps; each is then created as it's added
+    StepsPlanned {
+        steps: Vec<StepSpec>,
+    },
+    StepCreated {
+        step_id: usize,
+        desc: String,
+        // Set for a branch, to the step it forks from
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parent: Option<usize>,
+    },
+    // Reasoning text as the provider returns it; appending the deltas of a step rebuilds it
+    TokenDelta {
+        step_id: usize,
+        delta: String,
+    },
+    // A critique or revised draft of a step processed with reflection. Only the first draft
+    // streams as token deltas.
+    StepRevised {
+        step_id: usize,
+        revision: Revision,
+    },
+    StepConcluded {
+        step_id: usize,
+        conclusion: Option<Conclusion>,
+    },
+    // The step's description or prompt was changed
+    StepEdited {
+        step_id: usize,
+        desc: String,
+    },
+    StepDeleted {
+        step_id: usize,
+    },
+    // `step_id` became the path taken among the branches of `parent`
+    BranchChosen {
+        parent: usize,
+        step_id: usize,
+    },
+    Anchored {
+        step_id: usize,
+        tx_hash: String,
+        block_number: Option<u64>,
+        digest: String,
+    },
+    SvgReady {
+        svg_url: String,
+    },
+    NftMinted {
+        nft: NftRecord,
+    },
+}
+
+impl DreamEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DreamEventKind::DreamCreated { .. } => "dream-created",
+            DreamEventKind::StepsPlanned { .. } => "steps-planned",
+            DreamEventKind::StepCreated { .. } => "step-created",
+            DreamEventKind::TokenDelta { .. } => "token-delta",
+            DreamEventKind::StepRevised { .. } => "step-revised",
+            DreamEventKind::StepConcluded { .. } => "step-concluded",
+            DreamEventKind::StepEdited { .. } => "step-edited",
+            DreamEventKind::StepDeleted { .. } => "step-deleted",
+            DreamEventKind::BranchChosen { .. } => "branch-chosen",
+            DreamEventKind::Anchored { .. } => "anchored",
+            DreamEventKind::SvgReady { .. } => "svg-ready",
+            DreamEventKind::NftMinted { .. } => "nft-minted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DreamEvent {
+    pub dream_id: String,
+    #[serde(flatten)]
+    pub kind: DreamEventKind,
+}
+
+impl DreamEvent {
+    // One Server-Sent Events frame, named after the event type
+    pub fn to_sse(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
+        format!("event: {}\ndata: {}\n\n", self.kind.name(), data)
+    }
+}
+
+// Fan-out of every dream's events; subscribers filter by dream ID themselves
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<DreamEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER);
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DreamEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn sink(&self, dream_id: &str) -> EventSink {
+        EventSink {
+            dream_id: dream_id.to_string(),
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+// Publishes events on behalf of a single dream
+#[derive(Clone)]
+pub struct EventSink {
+    dream_id: String,
+    tx: broadcast::Sender<DreamEvent>,
+}
+
+impl EventSink {
+    pub fn emit(&self, kind: DreamEventKind) {
+        // Nobody listening is not an error
+        let _ = self.tx.send(DreamEvent {
+            dream_id: self.dream_id.clone(),
+            kind,
+        });
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+// Multicodec and multihash codes used to build a CIDv1
+const CID_VERSION: u8 = 0x01;
+const RAW_CODEC: u8 = 0x55;
+const SHA2_256: u8 = 0x12;
+const SHA2_256_LEN: u8 = 0x20;
+
+// Kubo's default chunk size; anything larger would be split into a dag-pb tree on upload
+const MAX_RAW_BLOCK: usize = 256 * 1024;
+
+// CIDv1 of `data` as a single raw block: base32 multibase of
+// <version><raw codec><sha2-256 multihash>. This matches what `ipfs add --cid-version=1
+// --raw-leaves` produces for blobs that fit in one chunk, which covers SVGs and metadata.
+pub fn cid(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+
+    let mut bytes = vec![CID_VERSION, RAW_CODEC, SHA2_256, SHA2_256_LEN];
+    bytes.extend_from_slice(&digest);
+
+    format!("b{}", BASE32_NOPAD.encode(&bytes).to_lowercase())
+}
+
+#[derive(Deserialize)]
+struct AddResponse {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+// Pins blobs on a Kubo node through its HTTP RPC API
+pub struct KuboClient {
+    api_url: String,
+    http: reqwest::Client,
+}
+
+impl KuboClient {
+    // `api_url` is the Kubo HTTP API, e.g. http://127.0.0.1:5001 (ipfs.api_url, IPFS_API_URL)
+    pub fn new(api_url: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    // Add and pin `data`, returning the CID the node assigned to it
+    pub async fn add(&self, data: Vec<u8>, content_type: &str) -> Result<String> {
+        if data.len() > MAX_RAW_BLOCK {
+            return Err(anyhow::anyhow!(
+                "Blob of {} bytes is larger than a single IPFS block",
+                data.len()
+            ));
+        }
+
+        let part = reqwest::multipart::Part::bytes(data)
+            .file_name("blob")
+            .mime_str(content_type)?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        let response = self
+            .http
+            .post(format!("{}/api/v0/add", self.api_url))
+            .query(&[
+                ("cid-version", "1"),
+                ("raw-leaves", "true"),
+                ("pin", "true"),
+            ])
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AddResponse>()
+            .await?;
+
+        Ok(response.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_input_has_the_well_known_cid() {
+        assert_eq!(
+            cid(b""),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+    }
+
+    #[test]
+    fn cid_matches_ipfs_add_raw_leaves() {
+        // `printf 'hello world' | ipfs add --cid-version=1 --raw-leaves -Q`
+        assert_eq!(
+            cid(b"hello world"),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+    }
+}
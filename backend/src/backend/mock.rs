@@ -0,0 +1,292 @@
+use alith::{Completion, CompletionError, Request, ResponseContent, ResponseToolCalls, ToolCall};
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+const WORDS: &[&str] = &[
+    "luminous",
+    "lattice",
+    "memory",
+    "signal",
+    "horizon",
+    "validator",
+    "echo",
+    "drift",
+    "consensus",
+    "fragment",
+    "tide",
+    "ledger",
+    "whisper",
+    "orbit",
+    "rollup",
+    "prism",
+    "thread",
+    "silence",
+    "gradient",
+    "bridge",
+    "pulse",
+    "cascade",
+    "mirror",
+    "seed",
+    "canopy",
+    "entropy",
+];
+
+const PALETTE: &[&str] = &[
+    "#7f5af0", "#2cb67d", "#ff8906", "#e53170", "#3da9fc", "#f25f4c", "#fffffe", "#94a1b2",
+];
+
+// A canned response returned whenever the prompt contains `pattern`
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockRule {
+    pub pattern: String,
+    pub response: String,
+}
+
+impl MockRule {
+    fn matches(&self, prompt: &str) -> bool {
+        prompt.to_lowercase().contains(&self.pattern.to_lowercase())
+    }
+}
+
+// Load canned responses from a JSON file: `[{"pattern": "...", "response": "..."}]`
+pub fn load_rules(path: impl AsRef<Path>) -> Result<Vec<MockRule>> {
+    let path = path.as_ref();
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read mock script {}: {}", path.display(), e))?;
+    let rules = serde_json::from_str(&raw)
+        .map_err(|e| anyhow::anyhow!("Invalid mock script {}: {}", path.display(), e))?;
+    Ok(rules)
+}
+
+// Deterministic stand-in for a real LLM. Scripted rules are checked first, in order;
+// anything unmatched gets seeded "random prose" shaped like the answer the prompt asks for.
+pub struct MockCompletion {
+    rules: Arc<Vec<MockRule>>,
+    rng: StdRng,
+}
+
+impl MockCompletion {
+    pub fn new(rules: Arc<Vec<MockRule>>, seed: u64) -> Self {
+        Self {
+            rules,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn respond(&mut self, prompt: &str) -> String {
+        if let Some(rule) = self.rules.iter().find(|r| r.matches(prompt)) {
+            return rule.response.clone();
+        }
+
+        if let Some(count) = parse_plan(prompt) {
+            let plan = self.plan(count);
+            self.answer(prompt, plan)
+        } else if let Some(count) = parse_count(prompt, "Propose exactly ") {
+            let branches = self.branches(count);
+            self.answer(prompt, branches)
+        } else if let Some(count) = parse_count(prompt, "Score each of these ") {
+            let scores = (0..count)
+                .map(|_| Value::from(self.rng.random_range(1..=10)))
+                .collect();
+            self.answer(prompt, Value::Array(scores))
+        } else if prompt.contains("Respond ONLY with valid SVG code") {
+            self.svg()
+        } else if prompt.contains("Respond with ONLY the title")
+            || prompt.contains("Respond with ONLY the theme")
+        {
+            self.title()
+        } else if prompt.contains("TRUE or FALSE") {
+            let verdict = self.rng.random_bool(0.5);
+            self.answer(prompt, Value::Bool(verdict))
+        } else if let Some(options) = parse_choices(prompt) {
+            let selected = options.choose(&mut self.rng).cloned().unwrap_or_default();
+            self.answer(prompt, Value::String(selected))
+        } else if let Some((min, max)) = parse_range(prompt) {
+            let value = self.rng.random_range(min..=max);
+            self.answer(prompt, Value::from(value))
+        } else if prompt.contains("in one sentence") {
+            self.sentence()
+        } else {
+            format!("{}\n\nConclusion: {}", self.prose(5), self.sentence())
+        }
+    }
+
+    // Typed steps ask for a JSON object; older prompts expect the answer on the last line
+    fn answer(&mut self, prompt: &str, answer: Value) -> String {
+        let reasoning = self.prose(3);
+
+        if prompt.contains("Respond ONLY with a JSON object") {
+            let confidence = f64::from(self.rng.random_range(50..100u8)) / 100.0;
+            serde_json::json!({
+                "reasoning": reasoning,
+                "answer": answer,
+                "confidence": confidence,
+            })
+            .to_string()
+        } else {
+            let answer = match answer {
+                Value::Bool(verdict) => if verdict { "TRUE" } else { "FALSE" }.to_string(),
+                Value::String(selected) => selected,
+                other => other.to_string(),
+            };
+            format!("{}\n\n{}", reasoning, answer)
+        }
+    }
+
+    fn word(&mut self) -> &'static str {
+        WORDS.choose(&mut self.rng).copied().unwrap_or("dream")
+    }
+
+    fn sentence(&mut self) -> String {
+        let len = self.rng.random_range(6..12);
+        let words: Vec<&str> = (0..len).map(|_| self.word()).collect();
+        let sentence = words.join(" ");
+        format!("{}{}.", sentence[..1].to_uppercase(), &sentence[1..])
+    }
+
+    fn prose(&mut self, sentences: usize) -> String {
+        (0..sentences)
+            .map(|_| self.sentence())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn title(&mut self) -> String {
+        format!("The {} of {}", self.word(), self.word())
+    }
+
+    // A plan that cycles through the step kinds, so every kind gets exercised
+    fn plan(&mut self, count: usize) -> Value {
+        let steps = (0..count)
+            .map(|i| {
+                let topic = self.word();
+                let description = format!("The {} of {}", self.word(), topic);
+                match i % 4 {
+                    0 => serde_json::json!({
+                        "kind": "open",
+                        "description": description,
+                        "prompt": format!("What does {{theme}} reveal about its {}?", topic),
+                    }),
+                    1 => serde_json::json!({
+                        "kind": "boolean",
+                        "description": description,
+                        "prompt": format!("Given {{previous}}, {{theme}} depends on its {}.", topic),
+                    }),
+                    2 => serde_json::json!({
+                        "kind": "choice",
+                        "description": description,
+                        "prompt": format!("Which {} matters most to {{theme}}?", topic),
+                        "options": WORDS.choose_multiple(&mut self.rng, 3).collect::<Vec<_>>(),
+                    }),
+                    _ => serde_json::json!({
+                        "kind": "numeric",
+                        "description": description,
+                        "prompt": format!("How strong is the {} of {{theme}}?", topic),
+                        "min": 1,
+                        "max": 10,
+                    }),
+                }
+            })
+            .collect();
+        Value::Array(steps)
+    }
+
+    // Continuations named after distinct words, so they never repeat one another
+    fn branches(&mut self, count: usize) -> Value {
+        let branches = WORDS
+            .choose_multiple(&mut self.rng, count)
+            .map(|word| {
+                serde_json::json!({
+                    "description": format!("Follow the {}", word),
+                    "prompt": format!("Where does the {} lead from here?", word),
+                })
+            })
+            .collect();
+        Value::Array(branches)
+    }
+
+    fn svg(&mut self) -> String {
+        let mut shapes = String::new();
+        for _ in 0..self.rng.random_range(3..7) {
+            let color = PALETTE.choose(&mut self.rng).copied().unwrap_or("#ffffff");
+            shapes.push_str(&format!(
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}" fill-opacity="0.6" />"#,
+                self.rng.random_range(40..260),
+                self.rng.random_range(40..260),
+                self.rng.random_range(10..80),
+                color
+            ));
+        }
+        format!(
+            r#"<svg width="300" height="300" xmlns="http://www.w3.org/2000/svg">{}</svg>"#,
+            shapes
+        )
+    }
+}
+
+// "...from the following choices: a, b, c\n\n..."
+fn parse_choices(prompt: &str) -> Option<Vec<String>> {
+    let marker = "following choices: ";
+    let start = prompt.find(marker)? + marker.len();
+    let line = prompt[start..].lines().next()?;
+    let options: Vec<String> = line
+        .split(", ")
+        .map(|o| o.trim().to_string())
+        .filter(|o| !o.is_empty())
+        .collect();
+    (!options.is_empty()).then_some(options)
+}
+
+// "Plan a chain of thought of exactly {count} reasoning steps..."
+fn parse_plan(prompt: &str) -> Option<usize> {
+    parse_count(prompt, "Plan a chain of thought of exactly ")
+}
+
+// The number right after `marker`, e.g. "Propose exactly {count} alternative continuations"
+fn parse_count(prompt: &str, marker: &str) -> Option<usize> {
+    let start = prompt.find(marker)? + marker.len();
+    prompt[start..].split_whitespace().next()?.parse().ok()
+}
+
+// "...a numeric value between {min} and {max} (inclusive)..."
+fn parse_range(prompt: &str) -> Option<(i32, i32)> {
+    let marker = "numeric value between ";
+    let start = prompt.find(marker)? + marker.len();
+    let mut parts = prompt[start..].split_whitespace();
+    let min = parts.next()?.parse::<i32>().ok()?;
+    let _and = parts.next()?;
+    let max = parts.next()?.parse::<i32>().ok()?;
+    (min <= max).then_some((min, max))
+}
+
+pub struct MockResponse {
+    content: String,
+}
+
+impl ResponseContent for MockResponse {
+    fn content(&self) -> String {
+        self.content.clone()
+    }
+}
+
+impl ResponseToolCalls for MockResponse {
+    fn toolcalls(&self) -> Vec<ToolCall> {
+        Vec::new()
+    }
+}
+
+impl Completion for MockCompletion {
+    type Response = MockResponse;
+
+    async fn completion(&mut self, request: Request) -> Result<Self::Response, CompletionError> {
+        Ok(MockResponse {
+            content: self.respond(&request.prompt),
+        })
+    }
+}
@@ -1,2 +1,15 @@
+pub mod actor;
 pub mod api;
+pub mod chain;
 pub mod chain_of_thought;
+pub mod conclusion;
+pub mod config;
+pub mod continuous;
+pub mod events;
+pub mod ipfs;
+pub mod mock;
+pub mod pipeline;
+pub mod provider;
+pub mod schedule;
+pub mod storage;
+pub mod structured;
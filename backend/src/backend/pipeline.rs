@@ -0,0 +1,406 @@
+use crate::backend::chain_of_thought::{self, ChainOfThought};
+use crate::backend::provider::ReportsUsage;
+use crate::backend::structured;
+use alith::Completion;
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Steps the agent plans for a dream that doesn't pick its own, and the most it may ask for
+pub const DEFAULT_PLAN_STEPS: usize = 4;
+pub const MAX_PLAN_STEPS: usize = 8;
+
+// Named pipelines, by name
+pub type Pipelines = BTreeMap<String, Vec<StepSpec>>;
+
+// Which kind of reasoning a step asks for, with the bounds of its answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StepKind {
+    Open,
+    Boolean,
+    Choice {
+        /// The options to pick from
+        options: Vec<String>,
+    },
+    Numeric {
+        /// Smallest allowed answer
+        min: i32,
+        /// Largest allowed answer
+        max: i32,
+    },
+}
+
+// One step of a pipeline. `prompt` may use {theme} for the dream's theme and {previous} for
+// the conclusion of the step before it, or of the steps it depends on; without a prompt the
+// step asks about its description.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StepSpec {
+    /// Short name of the step
+    pub description: String,
+    #[serde(flatten)]
+    pub kind: StepKind,
+    /// The question the step asks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    // Answers a typed step samples and votes over; left out of the planning schema so the
+    // agent doesn't pick its own sample counts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(skip)]
+    pub samples: Option<usize>,
+    // Critique-and-revise rounds of an open step, left out of the planning schema likewise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(skip)]
+    pub reflect: Option<usize>,
+    // Positions (from 0) of the earlier or later steps in the same list this one builds on.
+    // Steps that don't depend on each other may run at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(skip)]
+    pub depends_on: Option<Vec<usize>>,
+}
+
+impl StepSpec {
+    fn new(description: &str, kind: StepKind, prompt: &str) -> Self {
+        Self {
+            description: description.to_string(),
+            kind,
+            prompt: Some(prompt.to_string()),
+            samples: None,
+            reflect: None,
+            depends_on: None,
+        }
+    }
+
+    // An open-ended step about `description`, as plain `reasoning_steps` ask for
+    pub fn open(description: String) -> Self {
+        Self {
+            description,
+            kind: StepKind::Open,
+            prompt: None,
+            samples: None,
+            reflect: None,
+            depends_on: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.description.trim().is_empty() {
+            return Err("description must not be empty".to_string());
+        }
+        if self.prompt.as_ref().is_some_and(|p| p.trim().is_empty()) {
+            return Err("prompt must not be empty".to_string());
+        }
+        if self.samples.is_some() {
+            if self.kind == StepKind::Open {
+                return Err("samples only applies to boolean, choice and numeric steps".to_string());
+            }
+            structured::sample_count(self.samples)?;
+        }
+        if self.reflect.is_some() {
+            if self.kind != StepKind::Open {
+                return Err("reflect only applies to open steps".to_string());
+            }
+            chain_of_thought::reflect_rounds(self.reflect)?;
+        }
+
+        match &self.kind {
+            StepKind::Open | StepKind::Boolean => Ok(()),
+            StepKind::Choice { options } => {
+                if options.len() < 2 {
+                    return Err("choice steps need at least 2 options".to_string());
+                }
+                if options.iter().any(|o| o.trim().is_empty()) {
+                    return Err("choice options must not be empty".to_string());
+                }
+                if options
+                    .iter()
+                    .enumerate()
+                    .any(|(i, o)| options[..i].contains(o))
+                {
+                    return Err("choice options must be distinct".to_string());
+                }
+                Ok(())
+            }
+            StepKind::Numeric { min, max } => {
+                if min > max {
+                    return Err(format!("numeric min {} is greater than max {}", min, max));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn render(&self, theme: &str, previous: &str) -> String {
+        match &self.prompt {
+            Some(prompt) => prompt
+                .replace("{theme}", theme)
+                .replace("{previous}", previous),
+            None => format!("{}\n\nTheme: {}", self.description, theme),
+        }
+    }
+}
+
+// How a request picks the steps of its dream: plain open-ended `reasoning_steps`, full step
+// `steps`, or the name of a server-side `pipeline`. At most one may be given; with none the
+// agent plans `plan_steps` steps from the dream's theme.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_steps: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<Vec<StepSpec>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_steps: Option<usize>,
+}
+
+// The steps a dream runs: given up front, or planned by the agent once the dream exists
+#[derive(Debug, Clone)]
+pub enum StepPlan {
+    Fixed(Vec<StepSpec>),
+    Planned(usize),
+}
+
+impl StepSource {
+    pub fn resolve(&self, pipelines: &Pipelines) -> Result<StepPlan, String> {
+        let given = [
+            self.reasoning_steps.is_some(),
+            self.steps.is_some(),
+            self.pipeline.is_some(),
+        ]
+        .into_iter()
+        .filter(|given| *given)
+        .count();
+        if given > 1 {
+            return Err("Give only one of reasoning_steps, steps or pipeline".to_string());
+        }
+
+        if given == 0 {
+            let count = self.plan_steps.unwrap_or(DEFAULT_PLAN_STEPS);
+            if !(1..=MAX_PLAN_STEPS).contains(&count) {
+                return Err(format!(
+                    "plan_steps must be between 1 and {}",
+                    MAX_PLAN_STEPS
+                ));
+            }
+            return Ok(StepPlan::Planned(count));
+        }
+        if self.plan_steps.is_some() {
+            return Err("plan_steps only applies when the agent plans the steps".to_string());
+        }
+
+        let steps = if let Some(descriptions) = &self.reasoning_steps {
+            descriptions.iter().cloned().map(StepSpec::open).collect()
+        } else if let Some(steps) = &self.steps {
+            steps.clone()
+        } else {
+            let name = self.pipeline.as_deref().unwrap_or_default();
+            pipelines.get(name).cloned().ok_or_else(|| {
+                format!(
+                    "Unknown pipeline '{}', expected one of: {}",
+                    name,
+                    pipelines.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            })?
+        };
+
+        validate_steps(&steps)?;
+        Ok(StepPlan::Fixed(steps))
+    }
+}
+
+pub fn validate_steps(steps: &[StepSpec]) -> Result<(), String> {
+    if steps.is_empty() {
+        return Err("A dream needs at least one step".to_string());
+    }
+    for (i, step) in steps.iter().enumerate() {
+        step.validate()
+            .map_err(|e| format!("Step {} ({}): {}", i + 1, step.description, e))?;
+
+        for dependency in step.depends_on.iter().flatten() {
+            if *dependency >= steps.len() {
+                return Err(format!(
+                    "Step {} ({}): depends_on {} is out of range, expected 0 to {}",
+                    i + 1,
+                    step.description,
+                    dependency,
+                    steps.len() - 1
+                ));
+            }
+            if *dependency == i {
+                return Err(format!(
+                    "Step {} ({}): a step can't depend on itself",
+                    i + 1,
+                    step.description
+                ));
+            }
+        }
+    }
+
+    let graph: BTreeMap<usize, Vec<usize>> = steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| (i, step.depends_on.clone().unwrap_or_default()))
+        .collect();
+    if let Some(cycle) = chain_of_thought::find_cycle(&graph) {
+        return Err(format!(
+            "Steps depend on each other in a cycle: {}",
+            cycle
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        ));
+    }
+    Ok(())
+}
+
+// Pipelines that ship with the server; the config file can add more or replace these
+pub fn builtin() -> Pipelines {
+    let general = vec![
+        StepSpec::new(
+            "Current state analysis",
+            StepKind::Open,
+            "What is the current state of {theme}?",
+        ),
+        StepSpec::new(
+            "Future possibilities",
+            StepKind::Boolean,
+            "{theme} will grow significantly over the next five years.",
+        ),
+        StepSpec::new(
+            "Integration potential",
+            StepKind::Choice {
+                options: ["Technology", "Community", "Economics", "Culture"]
+                    .map(String::from)
+                    .to_vec(),
+            },
+            "Which area offers the most immediate value for {theme}?",
+        ),
+        StepSpec::new(
+            "Vision synthesis",
+            StepKind::Numeric { min: 1, max: 10 },
+            "In how many years will {theme} become mainstream?",
+        ),
+    ];
+
+    // The questions create-complete used to ask whatever the theme
+    let metis = vec![
+        StepSpec::new(
+            "Current state analysis",
+            StepKind::Open,
+            "What is the current state of Metis L2 technology in terms of scalability, security, and adoption?",
+        ),
+        StepSpec::new(
+            "Future possibilities",
+            StepKind::Boolean,
+            "Is AI enhancement essential for the future growth of Metis L2 solutions?",
+        ),
+        StepSpec::new(
+            "Integration potential",
+            StepKind::Choice {
+                options: [
+                    "Transaction optimization",
+                    "Smart contract enhancement",
+                    "Oracle improvements",
+                    "User experience",
+                ]
+                .map(String::from)
+                .to_vec(),
+            },
+            "Which area of integration offers the most immediate value for Metis L2?",
+        ),
+        StepSpec::new(
+            "Vision synthesis",
+            StepKind::Numeric { min: 1, max: 10 },
+            "In how many years will AI-enhanced blockchain solutions become mainstream on Metis?",
+        ),
+    ];
+
+    Pipelines::from([
+        ("general".to_string(), general),
+        ("metis".to_string(), metis),
+    ])
+}
+
+// Add `steps` to the dream and reason through them, independent steps side by side,
+// returning their step IDs
+pub async fn run<M: Completion + ReportsUsage>(
+    cot: &mut ChainOfThought<M>,
+    steps: &[StepSpec],
+) -> Result<Vec<usize>> {
+    let step_ids: Vec<usize> = steps
+        .iter()
+        .map(|step| cot.add_step(step.description.clone()))
+        .collect();
+
+    for (step, &step_id) in steps.iter().zip(&step_ids) {
+        if let Some(depends_on) = &step.depends_on {
+            let depends_on = depends_on.iter().map(|i| step_ids[*i]).collect();
+            cot.set_depends_on(step_id, Some(depends_on))?;
+        }
+    }
+
+    cot.process_graph(
+        step_ids
+            .iter()
+            .copied()
+            .zip(steps.iter().cloned())
+            .collect(),
+    )
+    .await?;
+    Ok(step_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(description: &str, depends_on: Option<Vec<usize>>) -> StepSpec {
+        StepSpec {
+            depends_on,
+            ..StepSpec::new(description, StepKind::Open, "What about {theme}?")
+        }
+    }
+
+    #[test]
+    fn independent_and_joined_steps_are_valid() {
+        let steps = [
+            step("Aroma", Some(vec![])),
+            step("Origin", Some(vec![])),
+            step("Pairing", Some(vec![0, 1])),
+            step("Verdict", None),
+        ];
+        assert_eq!(validate_steps(&steps), Ok(()));
+    }
+
+    #[test]
+    fn cyclic_dependencies_are_rejected() {
+        let steps = [
+            step("Aroma", Some(vec![2])),
+            step("Origin", Some(vec![0])),
+            step("Pairing", Some(vec![1])),
+        ];
+        assert_eq!(
+            validate_steps(&steps),
+            Err("Steps depend on each other in a cycle: 0 -> 2 -> 1 -> 0".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_dependencies_are_rejected() {
+        let out_of_range = [step("Aroma", None), step("Origin", Some(vec![2]))];
+        assert_eq!(
+            validate_steps(&out_of_range),
+            Err("Step 2 (Origin): depends_on 2 is out of range, expected 0 to 1".to_string())
+        );
+
+        let itself = [step("Aroma", Some(vec![0]))];
+        assert_eq!(
+            validate_steps(&itself),
+            Err("Step 1 (Aroma): a step can't depend on itself".to_string())
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::backend::mock::{self, MockCompletion, MockResponse, MockRule};
+use alith::{
+    Completion, CompletionError, CompletionResponse, LLM, Request, ResponseContent,
+    ResponseToolCalls, ToolCall,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// How the backend gets its completions, chosen once at startup
+#[derive(Clone)]
+pub enum ModelProvider {
+    OpenAi {
+        model_name: String,
+    },
+    Mock {
+        seed: u64,
+        rules: Arc<Vec<MockRule>>,
+    },
+}
+
+impl ModelProvider {
+    // DREAM_PROVIDER=mock switches to the offline provider, seeded with DREAM_MOCK_SEED
+    // and optionally scripted with the rules in DREAM_MOCK_SCRIPT; otherwise OpenAI's
+    // `model_name` is used
+    pub fn from_env(model_name: &str) -> Result<Self> {
+        let provider = std::env::var("DREAM_PROVIDER").unwrap_or_else(|_| "openai".to_string());
+
+        match provider.to_lowercase().as_str() {
+            "openai" => Ok(ModelProvider::OpenAi {
+                model_name: model_name.to_string(),
+            }),
+            "mock" => {
+                let seed = match std::env::var("DREAM_MOCK_SEED") {
+                    Ok(seed) => seed
+                        .parse::<u64>()
+                        .map_err(|_| anyhow::anyhow!("DREAM_MOCK_SEED must be an integer"))?,
+                    Err(_) => 42,
+                };
+                let rules = match std::env::var("DREAM_MOCK_SCRIPT") {
+                    Ok(path) => mock::load_rules(path)?,
+                    Err(_) => Vec::new(),
+                };
+                Ok(ModelProvider::Mock {
+                    seed,
+                    rules: Arc::new(rules),
+                })
+            }
+            other => Err(anyhow::anyhow!(
+                "Unknown DREAM_PROVIDER '{}', expected 'openai' or 'mock'",
+                other
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ModelProvider::OpenAi { model_name } => model_name,
+            ModelProvider::Mock { .. } => "mock",
+        }
+    }
+
+    pub fn build(&self) -> Result<DreamModel> {
+        match self {
+            ModelProvider::OpenAi { model_name } => Ok(DreamModel::new(ModelClient::OpenAi(
+                LLM::from_model_name(model_name)?,
+            ))),
+            ModelProvider::Mock { seed, rules } => Ok(DreamModel::new(ModelClient::Mock(
+                Box::new(MockCompletion::new(rules.clone(), *seed)),
+            ))),
+        }
+    }
+
+    // A model for the worker with the given index; mock workers each get a seed of their own
+    // so their answers don't repeat the dream's
+    pub fn build_worker(&self, index: usize) -> Result<DreamModel> {
+        match self {
+            ModelProvider::Mock { seed, rules } => {
+                Ok(DreamModel::new(ModelClient::Mock(Box::new(
+                    MockCompletion::new(rules.clone(), seed.wrapping_add(index as u64 + 1)),
+                ))))
+            }
+            _ => self.build(),
+        }
+    }
+}
+
+// Tokens spent on one completion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+// A model that can say what its latest completion cost. Agents only hand back the reply's
+// text, so the usage is read from the agent's model right after prompting it.
+pub trait ReportsUsage {
+    fn last_usage(&self) -> Option<TokenUsage>;
+}
+
+pub struct DreamModel {
+    client: ModelClient,
+    last_usage: Option<TokenUsage>,
+}
+
+enum ModelClient {
+    OpenAi(LLM),
+    Mock(Box<MockCompletion>),
+}
+
+impl DreamModel {
+    fn new(client: ModelClient) -> Self {
+        Self {
+            client,
+            last_usage: None,
+        }
+    }
+}
+
+impl ReportsUsage for DreamModel {
+    fn last_usage(&self) -> Option<TokenUsage> {
+        self.last_usage
+    }
+}
+
+pub enum DreamModelResponse {
+    OpenAi(Box<CompletionResponse>),
+    Mock(MockResponse),
+}
+
+impl ResponseContent for DreamModelResponse {
+    fn content(&self) -> String {
+        match self {
+            DreamModelResponse::OpenAi(response) => response.content(),
+            DreamModelResponse::Mock(response) => response.content(),
+        }
+    }
+}
+
+impl ResponseToolCalls for DreamModelResponse {
+    fn toolcalls(&self) -> Vec<ToolCall> {
+        match self {
+            DreamModelResponse::OpenAi(response) => response.toolcalls(),
+            DreamModelResponse::Mock(response) => response.toolcalls(),
+        }
+    }
+}
+
+impl Completion for DreamModel {
+    type Response = DreamModelResponse;
+
+    async fn completion(&mut self, request: Request) -> Result<Self::Response, CompletionError> {
+        self.last_usage = None;
+        match &mut self.client {
+            ModelClient::OpenAi(llm) => {
+                let response = llm.completion(request).await?;
+                self.last_usage = Some(TokenUsage {
+                    prompt_tokens: response.token_usage.prompt_tokens,
+                    completion_tokens: response.token_usage.completion_tokens,
+                    total_tokens: response.token_usage.total_tokens,
+                });
+                Ok(DreamModelResponse::OpenAi(Box::new(response)))
+            }
+            ModelClient::Mock(mock) => {
+                // Words stand in for tokens, so offline runs still report a usage
+                let prompt_tokens = word_count(&request.preamble) + word_count(&request.prompt);
+                let response = mock.completion(request).await?;
+                let completion_tokens = word_count(&response.content());
+                self.last_usage = Some(TokenUsage {
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
+                });
+                Ok(DreamModelResponse::Mock(response))
+            }
+        }
+    }
+}
+
+fn word_count(text: &str) -> u32 {
+    text.split_whitespace().count() as u32
+}
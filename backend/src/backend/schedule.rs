@@ -0,0 +1,96 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::str::FromStr;
+
+// Parse a cron expression, evaluated in UTC. Standard five-field expressions
+// ("0 9 * * *") are accepted as well as the cron crate's own format, which starts with a
+// seconds field and may end with a years field, and shorthands like "@daily".
+pub fn parse(expression: &str) -> Result<cron::Schedule> {
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+
+    cron::Schedule::from_str(&expression)
+        .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", expression, e))
+}
+
+pub fn next_run(schedule: &cron::Schedule) -> Option<DateTime<Utc>> {
+    schedule.upcoming(Utc).next()
+}
+
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The next `count` runs after `start`, formatted
+    fn runs(expression: &str, start: &str, count: usize) -> Vec<String> {
+        let start = DateTime::parse_from_rfc3339(start)
+            .unwrap()
+            .with_timezone(&Utc);
+        parse(expression)
+            .unwrap()
+            .after(&start)
+            .take(count)
+            .map(format_time)
+            .collect()
+    }
+
+    #[test]
+    fn five_fields_run_on_the_minute() {
+        assert_eq!(
+            runs("0 9 * * *", "2026-10-17T10:00:00Z", 2),
+            ["2026-10-18T09:00:00Z", "2026-10-19T09:00:00Z"]
+        );
+        assert_eq!(
+            runs("*/15 * * * *", "2026-10-17T10:07:30Z", 3),
+            [
+                "2026-10-17T10:15:00Z",
+                "2026-10-17T10:30:00Z",
+                "2026-10-17T10:45:00Z"
+            ]
+        );
+        assert_eq!(
+            runs("30 6 1 * *", "2026-10-17T00:00:00Z", 2),
+            ["2026-11-01T06:30:00Z", "2026-12-01T06:30:00Z"]
+        );
+    }
+
+    #[test]
+    fn days_of_the_week_count_from_sunday() {
+        // 2026-10-17 is a Saturday
+        assert_eq!(
+            runs("0 9 * * MON-FRI", "2026-10-17T10:00:00Z", 2),
+            ["2026-10-19T09:00:00Z", "2026-10-20T09:00:00Z"]
+        );
+        assert_eq!(
+            runs("0 9 * * 1", "2026-10-17T10:00:00Z", 1),
+            ["2026-10-18T09:00:00Z"]
+        );
+    }
+
+    #[test]
+    fn other_forms_are_accepted() {
+        assert_eq!(
+            runs("30 0 9 * * *", "2026-10-17T10:00:00Z", 1),
+            ["2026-10-18T09:00:30Z"]
+        );
+        assert_eq!(
+            runs("@daily", "2026-10-17T10:00:00Z", 1),
+            ["2026-10-18T00:00:00Z"]
+        );
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert!(parse("0 9 * *").is_err());
+        assert!(parse("0 25 * * *").is_err());
+        assert!(parse("every day").is_err());
+    }
+}
@@ -0,0 +1,532 @@
+use crate::backend::chain_of_thought::ReasoningStep;
+use crate::backend::pipeline::{StepSource, StepSpec};
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// Everything needed to rebuild a ChainOfThought after a restart
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredDream {
+    pub id: String,
+    pub title: Option<String>,
+    pub theme: Option<String>,
+    pub steps: Vec<ReasoningStep>,
+    pub plan: Option<Vec<StepSpec>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NftRecord {
+    // CIDs of the rendered SVG and of the metadata JSON pointing at it
+    pub ipfs_cid: String,
+    pub metadata_cid: String,
+    pub token_uri: String,
+    pub token_id: String,
+    pub owner: String,
+    pub transaction_hash: String,
+    // Set once an anchored step of the dream changed or was deleted after minting, so the
+    // minted SVG and metadata no longer match the dream
+    #[serde(default)]
+    pub stale: bool,
+}
+
+// Content-addressed bytes served back at /ipfs/{cid}
+#[derive(Debug, Clone)]
+pub struct Blob {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+// A create-complete run repeated on a cron schedule (in UTC)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DreamSchedule {
+    pub id: String,
+    pub cron: String,
+    pub theme: String,
+    // Resolved on every run, so a schedule follows changes to the pipeline it names
+    #[serde(flatten)]
+    pub steps: StepSource,
+    pub auto_mint: bool,
+    pub created_at: String,
+    pub last_run_at: Option<String>,
+    pub last_dream_id: Option<String>,
+    pub last_error: Option<String>,
+}
+
+// Schema changes, applied in order and tracked with `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS dreams (
+        id TEXT PRIMARY KEY,
+        title TEXT,
+        theme TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS steps (
+        dream_id TEXT NOT NULL REFERENCES dreams(id) ON DELETE CASCADE,
+        step_id INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (dream_id, step_id)
+    );
+    CREATE TABLE IF NOT EXISTS svgs (
+        dream_id TEXT PRIMARY KEY REFERENCES dreams(id) ON DELETE CASCADE,
+        svg TEXT NOT NULL,
+        rendered_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS nfts (
+        dream_id TEXT PRIMARY KEY REFERENCES dreams(id) ON DELETE CASCADE,
+        ipfs_cid TEXT NOT NULL,
+        transaction_hash TEXT NOT NULL,
+        minted_at TEXT NOT NULL
+    );",
+    "ALTER TABLE nfts ADD COLUMN token_uri TEXT NOT NULL DEFAULT '';
+    ALTER TABLE nfts ADD COLUMN token_id TEXT NOT NULL DEFAULT '';
+    ALTER TABLE nfts ADD COLUMN owner TEXT NOT NULL DEFAULT '';",
+    "CREATE TABLE IF NOT EXISTS blobs (
+        cid TEXT PRIMARY KEY,
+        content_type TEXT NOT NULL,
+        data BLOB NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    ALTER TABLE nfts ADD COLUMN metadata_cid TEXT NOT NULL DEFAULT '';",
+    // Conclusions used to be plain strings; keep them as open-ended text conclusions
+    "UPDATE steps SET data = json_set(
+        data,
+        '$.conclusion',
+        json_object('kind', 'open', 'text', json_extract(data, '$.conclusion'))
+    )
+    WHERE json_type(data, '$.conclusion') = 'text';",
+    "CREATE TABLE IF NOT EXISTS schedules (
+        id TEXT PRIMARY KEY,
+        cron TEXT NOT NULL,
+        theme TEXT NOT NULL,
+        reasoning_steps TEXT,
+        auto_mint INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        last_run_at TEXT,
+        last_dream_id TEXT,
+        last_error TEXT
+    );",
+    "ALTER TABLE schedules ADD COLUMN steps TEXT;
+    ALTER TABLE schedules ADD COLUMN pipeline TEXT;",
+    "ALTER TABLE dreams ADD COLUMN plan TEXT;",
+    "ALTER TABLE nfts ADD COLUMN stale INTEGER NOT NULL DEFAULT 0;",
+];
+
+// Fixed-width timestamps so they compare correctly as strings in SQL
+pub fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+pub trait DreamStore: Send + Sync {
+    // Insert or replace the dream and all of its steps
+    fn save_dream(&self, dream: &StoredDream) -> Result<()>;
+    fn load_dream(&self, dream_id: &str) -> Result<Option<StoredDream>>;
+    fn save_svg(&self, dream_id: &str, svg: &str) -> Result<()>;
+    // Returns the stored SVG only if it was rendered after the last step change
+    fn load_svg(&self, dream_id: &str) -> Result<Option<String>>;
+    fn save_nft(&self, dream_id: &str, nft: &NftRecord) -> Result<()>;
+    fn load_nft(&self, dream_id: &str) -> Result<Option<NftRecord>>;
+    // Flag the dream's NFT, if it has one, as out of date
+    fn mark_nft_stale(&self, dream_id: &str) -> Result<()>;
+    // Blobs are immutable, so saving an existing CID is a no-op
+    fn save_blob(&self, cid: &str, blob: &Blob) -> Result<()>;
+    fn load_blob(&self, cid: &str) -> Result<Option<Blob>>;
+    fn save_schedule(&self, schedule: &DreamSchedule) -> Result<()>;
+    fn list_schedules(&self) -> Result<Vec<DreamSchedule>>;
+    // Returns false if there was no such schedule
+    fn delete_schedule(&self, schedule_id: &str) -> Result<bool>;
+    // Record the outcome of a run; a deleted schedule stays deleted
+    fn record_schedule_run(
+        &self,
+        schedule_id: &str,
+        dream_id: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()>;
+}
+
+// Calls block the calling thread. Each is a few indexed statements on a local file, shorter
+// than the model calls around them, so they run in place rather than on a blocking pool.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        migrate(&mut conn)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // A panic mid-transaction drops the transaction, which rolls it back, so the connection
+    // is still consistent and a poisoned lock doesn't have to fail every later call
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i as i64 + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+impl DreamStore for SqliteStore {
+    fn save_dream(&self, dream: &StoredDream) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let now = now();
+        let plan = dream.plan.as_ref().map(serde_json::to_string).transpose()?;
+
+        tx.execute(
+            "INSERT INTO dreams (id, title, theme, plan, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title,
+                theme = excluded.theme,
+                plan = excluded.plan,
+                updated_at = excluded.updated_at",
+            params![dream.id, dream.title, dream.theme, plan, now],
+        )?;
+
+        tx.execute("DELETE FROM steps WHERE dream_id = ?1", params![dream.id])?;
+        for step in &dream.steps {
+            tx.execute(
+                "INSERT INTO steps (dream_id, step_id, data) VALUES (?1, ?2, ?3)",
+                params![dream.id, step.step_id as i64, serde_json::to_string(step)?],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_dream(&self, dream_id: &str) -> Result<Option<StoredDream>> {
+        let conn = self.conn();
+
+        let dream = conn
+            .query_row(
+                "SELECT title, theme, plan FROM dreams WHERE id = ?1",
+                params![dream_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, Option<String>>(2)?)),
+            )
+            .optional()?;
+
+        let Some((title, theme, plan)) = dream else {
+            return Ok(None);
+        };
+        let plan = plan.map(|plan| serde_json::from_str(&plan)).transpose()?;
+
+        let mut stmt =
+            conn.prepare("SELECT data FROM steps WHERE dream_id = ?1 ORDER BY step_id")?;
+        let steps = stmt
+            .query_map(params![dream_id], |row| row.get::<_, String>(0))?
+            .map(|data| Ok(serde_json::from_str::<ReasoningStep>(&data?)?))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(StoredDream {
+            id: dream_id.to_string(),
+            title,
+            theme,
+            steps,
+            plan,
+        }))
+    }
+
+    fn save_svg(&self, dream_id: &str, svg: &str) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT OR REPLACE INTO svgs (dream_id, svg, rendered_at) VALUES (?1, ?2, ?3)",
+            params![dream_id, svg, now()],
+        )?;
+        Ok(())
+    }
+
+    fn load_svg(&self, dream_id: &str) -> Result<Option<String>> {
+        let conn = self.conn();
+        let svg = conn
+            .query_row(
+                "SELECT s.svg FROM svgs s JOIN dreams d ON d.id = s.dream_id
+                WHERE s.dream_id = ?1 AND s.rendered_at >= d.updated_at",
+                params![dream_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(svg)
+    }
+
+    fn save_nft(&self, dream_id: &str, nft: &NftRecord) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT OR REPLACE INTO nfts
+                (dream_id, ipfs_cid, metadata_cid, token_uri, token_id, owner,
+                transaction_hash, stale, minted_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                dream_id,
+                nft.ipfs_cid,
+                nft.metadata_cid,
+                nft.token_uri,
+                nft.token_id,
+                nft.owner,
+                nft.transaction_hash,
+                nft.stale,
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_nft(&self, dream_id: &str) -> Result<Option<NftRecord>> {
+        let conn = self.conn();
+        let nft = conn
+            .query_row(
+                "SELECT ipfs_cid, metadata_cid, token_uri, token_id, owner, transaction_hash,
+                    stale
+                FROM nfts WHERE dream_id = ?1",
+                params![dream_id],
+                |row| {
+                    Ok(NftRecord {
+                        ipfs_cid: row.get(0)?,
+                        metadata_cid: row.get(1)?,
+                        token_uri: row.get(2)?,
+                        token_id: row.get(3)?,
+                        owner: row.get(4)?,
+                        transaction_hash: row.get(5)?,
+                        stale: row.get(6)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(nft)
+    }
+
+    fn mark_nft_stale(&self, dream_id: &str) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE nfts SET stale = 1 WHERE dream_id = ?1",
+            params![dream_id],
+        )?;
+        Ok(())
+    }
+
+    fn save_blob(&self, cid: &str, blob: &Blob) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT OR IGNORE INTO blobs (cid, content_type, data, created_at)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![cid, blob.content_type, blob.data, now()],
+        )?;
+        Ok(())
+    }
+
+    fn load_blob(&self, cid: &str) -> Result<Option<Blob>> {
+        let conn = self.conn();
+        let blob = conn
+            .query_row(
+                "SELECT content_type, data FROM blobs WHERE cid = ?1",
+                params![cid],
+                |row| {
+                    Ok(Blob {
+                        content_type: row.get(0)?,
+                        data: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(blob)
+    }
+
+    fn save_schedule(&self, schedule: &DreamSchedule) -> Result<()> {
+        let conn = self.conn();
+        let reasoning_steps = schedule
+            .steps
+            .reasoning_steps
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let steps = schedule
+            .steps
+            .steps
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO schedules
+                (id, cron, theme, reasoning_steps, steps, pipeline, auto_mint, created_at,
+                last_run_at, last_dream_id, last_error)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                schedule.id,
+                schedule.cron,
+                schedule.theme,
+                reasoning_steps,
+                steps,
+                schedule.steps.pipeline,
+                schedule.auto_mint,
+                schedule.created_at,
+                schedule.last_run_at,
+                schedule.last_dream_id,
+                schedule.last_error
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn list_schedules(&self) -> Result<Vec<DreamSchedule>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, cron, theme, reasoning_steps, steps, pipeline, auto_mint, created_at,
+                last_run_at, last_dream_id, last_error
+            FROM schedules ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                DreamSchedule {
+                    id: row.get(0)?,
+                    cron: row.get(1)?,
+                    theme: row.get(2)?,
+                    steps: StepSource {
+                        pipeline: row.get(5)?,
+                        ..StepSource::default()
+                    },
+                    auto_mint: row.get(6)?,
+                    created_at: row.get(7)?,
+                    last_run_at: row.get(8)?,
+                    last_dream_id: row.get(9)?,
+                    last_error: row.get(10)?,
+                },
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+
+        rows.map(|row| {
+            let (mut schedule, reasoning_steps, steps) = row?;
+            schedule.steps.reasoning_steps = reasoning_steps
+                .map(|steps| serde_json::from_str(&steps))
+                .transpose()?;
+            schedule.steps.steps = steps
+                .map(|steps| serde_json::from_str(&steps))
+                .transpose()?;
+            Ok(schedule)
+        })
+        .collect()
+    }
+
+    fn delete_schedule(&self, schedule_id: &str) -> Result<bool> {
+        let conn = self.conn();
+        let deleted = conn.execute("DELETE FROM schedules WHERE id = ?1", params![schedule_id])?;
+        Ok(deleted > 0)
+    }
+
+    fn record_schedule_run(
+        &self,
+        schedule_id: &str,
+        dream_id: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE schedules SET last_run_at = ?2, last_dream_id = ?3, last_error = ?4
+            WHERE id = ?1",
+            params![schedule_id, now(), dream_id, error],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::chain_of_thought::Conclusion;
+
+    fn dream() -> StoredDream {
+        let mut first = ReasoningStep::new(0, "Aroma".to_string());
+        first.reasoning = "It smells of rain.\nConclusion: petrichor".to_string();
+        first.conclusion = Some(Conclusion::Open {
+            text: "Petrichor".to_string(),
+        });
+        first.summary = Some("Petrichor".to_string());
+        first.confidence = Some(0.8);
+        first.spec = Some(StepSpec::open("Aroma".to_string()));
+
+        let mut second = ReasoningStep::new(1, "Pairing".to_string());
+        second.depends_on = Some(vec![0]);
+        second.parent = Some(0);
+        second.chosen = Some(true);
+
+        StoredDream {
+            id: "dream-1".to_string(),
+            title: Some("Tea in the rain".to_string()),
+            theme: Some("tea".to_string()),
+            steps: vec![first, second],
+            plan: Some(vec![StepSpec::open("Aroma".to_string())]),
+        }
+    }
+
+    #[test]
+    fn migrations_run_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        migrate(&mut conn).unwrap();
+
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn saved_dream_loads_back_equal() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        let dream = dream();
+        store.save_dream(&dream).unwrap();
+
+        assert_eq!(store.load_dream("dream-1").unwrap(), Some(dream));
+        assert_eq!(store.load_dream("dream-2").unwrap(), None);
+    }
+
+    #[test]
+    fn saving_again_replaces_the_steps() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        let mut dream = dream();
+        store.save_dream(&dream).unwrap();
+
+        dream.steps.pop();
+        dream.title = Some("Tea".to_string());
+        store.save_dream(&dream).unwrap();
+
+        assert_eq!(store.load_dream("dream-1").unwrap(), Some(dream));
+    }
+
+    #[test]
+    fn a_panic_holding_the_lock_does_not_break_the_store() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        std::thread::scope(|scope| {
+            let poisoned = scope.spawn(|| {
+                let _conn = store.conn();
+                panic!("panicked while holding the connection");
+            });
+            assert!(poisoned.join().is_err());
+        });
+        assert!(store.conn.is_poisoned());
+
+        let dream = dream();
+        store.save_dream(&dream).unwrap();
+        assert_eq!(store.load_dream("dream-1").unwrap(), Some(dream));
+    }
+}
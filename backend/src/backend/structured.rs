@@ -0,0 +1,149 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+
+// Tries per structured answer before it fails with ChainOfThoughtError::ParseFailed
+pub const MAX_ATTEMPTS: usize = 3;
+
+// Most answers a typed step may sample for a self-consistency vote
+pub const MAX_SAMPLES: usize = 9;
+
+// The JSON object typed steps ask the model for
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct StructuredAnswer<T> {
+    /// Step-by-step reasoning that leads to the answer
+    pub reasoning: String,
+    /// The final answer
+    pub answer: T,
+    /// How confident the answer is, from 0 (guess) to 1 (certain)
+    pub confidence: f64,
+}
+
+// The model never produced an answer that parsed and passed validation
+#[derive(Debug, Clone)]
+pub struct StructuredOutputError {
+    // None for an answer asked for the dream as a whole, like its plan or a step's branches
+    pub step_id: Option<usize>,
+    pub attempts: usize,
+    pub reason: String,
+    pub response: String,
+}
+
+impl fmt::Display for StructuredOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.step_id {
+            Some(step_id) => write!(
+                f,
+                "Step {} produced no valid answer after {} attempts: {}",
+                step_id, self.attempts, self.reason
+            ),
+            None => write!(
+                f,
+                "The agent produced no valid answer after {} attempts: {}",
+                self.attempts, self.reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StructuredOutputError {}
+
+// JSON schema of StructuredAnswer<T>, with `refine` adding constraints to the `answer`
+// property that the type alone can't express (allowed options, numeric bounds)
+pub fn schema<T: JsonSchema>(refine: impl FnOnce(&mut Value)) -> String {
+    let mut schema =
+        serde_json::to_value(schemars::schema_for!(StructuredAnswer<T>)).unwrap_or_default();
+    if let Some(answer) = schema.pointer_mut("/properties/answer") {
+        refine(answer);
+    }
+    serde_json::to_string_pretty(&schema).unwrap_or_default()
+}
+
+// Samples to vote over, one (no vote) unless asked for more
+pub fn sample_count(samples: Option<usize>) -> Result<usize, String> {
+    match samples {
+        None => Ok(1),
+        Some(samples) if (1..=MAX_SAMPLES).contains(&samples) => Ok(samples),
+        Some(_) => Err(format!("samples must be between 1 and {}", MAX_SAMPLES)),
+    }
+}
+
+pub fn instructions(schema: &str) -> String {
+    format!(
+        "Respond ONLY with a JSON object matching this JSON schema, with no other text:\n{}",
+        schema
+    )
+}
+
+// Parse the first JSON object in `response`, tolerating code fences and stray prose around it
+pub fn parse<T: DeserializeOwned>(response: &str) -> Result<StructuredAnswer<T>, String> {
+    let start = response
+        .find('{')
+        .ok_or_else(|| "no JSON object found".to_string())?;
+    let end = response
+        .rfind('}')
+        .filter(|end| *end > start)
+        .ok_or_else(|| "unterminated JSON object".to_string())?;
+
+    let answer: StructuredAnswer<T> = serde_json::from_str(&response[start..=end])
+        .map_err(|e| format!("invalid JSON answer: {}", e))?;
+
+    if !(0.0..=1.0).contains(&answer.confidence) {
+        return Err(format!("confidence {} is outside 0..=1", answer.confidence));
+    }
+
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_an_answer_wrapped_in_prose_and_fences() {
+        let response = "Here you go:\n```json\n{\"reasoning\": \"Water is wet.\", \"answer\": true, \"confidence\": 0.9}\n```\nHope that helps!";
+        let answer = parse::<bool>(response).unwrap();
+        assert_eq!(answer.reasoning, "Water is wet.");
+        assert!(answer.answer);
+        assert_eq!(answer.confidence, 0.9);
+    }
+
+    #[test]
+    fn rejects_replies_without_a_valid_answer() {
+        assert_eq!(
+            parse::<bool>("The answer is TRUE.").unwrap_err(),
+            "no JSON object found"
+        );
+        assert!(
+            parse::<i32>(r#"{"reasoning": "", "answer": "seven", "confidence": 0.5}"#)
+                .unwrap_err()
+                .starts_with("invalid JSON answer")
+        );
+        assert_eq!(
+            parse::<i32>(r#"{"reasoning": "", "answer": 7, "confidence": 1.5}"#).unwrap_err(),
+            "confidence 1.5 is outside 0..=1"
+        );
+    }
+
+    #[test]
+    fn schema_carries_the_refined_constraints() {
+        let schema = schema::<i32>(|answer| {
+            answer["minimum"] = serde_json::json!(1);
+            answer["maximum"] = serde_json::json!(10);
+        });
+        let schema: Value = serde_json::from_str(&schema).unwrap();
+        assert_eq!(schema["properties"]["answer"]["minimum"], 1);
+        assert_eq!(schema["properties"]["answer"]["maximum"], 10);
+        assert!(schema["properties"]["confidence"].is_object());
+    }
+
+    #[test]
+    fn sample_count_is_bounded() {
+        assert_eq!(sample_count(None), Ok(1));
+        assert_eq!(sample_count(Some(MAX_SAMPLES)), Ok(MAX_SAMPLES));
+        assert!(sample_count(Some(0)).is_err());
+        assert!(sample_count(Some(MAX_SAMPLES + 1)).is_err());
+    }
+}
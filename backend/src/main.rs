@@ -1,11 +1,91 @@
 use backend::backend::api::Backend;
+use backend::backend::chain::{ChainClient, ChainConfig};
+use backend::backend::config::Config;
+use backend::backend::ipfs::KuboClient;
+use backend::backend::provider::ModelProvider;
+use backend::backend::storage::SqliteStore;
+use std::sync::Arc;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     println!("🚀 Starting AI Dreamcatcher API Server");
 
+    // Read the config file and environment overrides, refusing to start on a bad setting
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Invalid configuration: {}", e);
+            return Ok(());
+        }
+    };
+    match &config.path {
+        Some(path) => println!("⚙️  Loaded configuration from {}", path.display()),
+        None => println!("⚙️  No config file, using defaults and environment variables"),
+    }
+
+    // Pick the completion provider (OpenAI by default, mock for offline runs)
+    let provider = match ModelProvider::from_env(&config.model.name) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("❌ Invalid model provider configuration: {}", e);
+            return Ok(());
+        }
+    };
+    println!("🧠 Using model provider: {}", provider.name());
+
+    // Open the dream database (created on first run)
+    let db_path = config.storage.db_path.clone();
+    let store = match SqliteStore::open(&db_path) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("❌ Failed to open dream database {}: {}", db_path, e);
+            return Ok(());
+        }
+    };
+    println!("💾 Persisting dreams to {}", db_path);
+
+    // Connect to the chain used for anchoring, if configured
+    let chain =
+        match ChainConfig::from_settings(&config.chain).map(|config| ChainClient::new(&config)) {
+            Some(Ok(chain)) => {
+                println!("⛓️  Anchoring reasoning steps on chain");
+                if !chain.can_mint() {
+                    println!("⛓️  DreamNFT contract not configured, minting disabled");
+                }
+                Some(Arc::new(chain))
+            }
+            None => {
+                println!("⛓️  Chain not configured, anchoring and minting disabled");
+                None
+            }
+            Some(Err(e)) => {
+                eprintln!("❌ Invalid chain configuration: {}", e);
+                return Ok(());
+            }
+        };
+
+    // Optionally pin minted SVGs and metadata on a Kubo node
+    let ipfs = config.ipfs.api_url.as_deref().map(KuboClient::new);
+    match &ipfs {
+        Some(kubo) => println!("🌐 Pinning NFT assets to IPFS via {}", kubo.api_url()),
+        None => println!("🌐 IPFS node not configured, NFT assets served from /ipfs only"),
+    }
+
+    // Decide how much of the chain so far each step gets to see
+    let context = config.model.context_window();
+    match &context {
+        Some(window) => println!(
+            "🔗 Feeding up to {} earlier conclusions ({} chars) into each step",
+            window.max_steps, window.max_chars
+        ),
+        None => println!("🔗 Steps are reasoned about without earlier conclusions"),
+    }
+
+    let bind = config.server.bind.clone();
+    let base_url = config.server.base_url.clone();
+
     // Initialize the backend
-    let backend = match Backend::new() {
+    let backend = match Backend::new(config, provider, Arc::new(store), chain, ipfs, context) {
         Ok(backend) => {
             println!("✅ Backend initialized successfully");
             backend
@@ -16,12 +96,26 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
-    println!("🌐 API server starting on http://127.0.0.1:8080");
+    println!(
+        "🌐 API server starting on {} (public URL {})",
+        bind, base_url
+    );
     println!("📝 Available endpoints:");
     println!("   POST /api/dreams - Create a new dream");
     println!("   GET /api/dreams/{{id}} - Get dream details");
     println!("   POST /api/dreams/{{id}}/steps - Add a reasoning step");
+    println!("   PATCH /api/dreams/{{id}}/steps/{{step_id}} - Edit a step");
+    println!("   DELETE /api/dreams/{{id}}/steps/{{step_id}} - Delete a step");
+    println!("   POST /api/dreams/{{id}}/steps/{{step_id}}/rerun - Run a step again");
+    println!("   GET /api/dreams/{{id}}/steps/{{step_id}}/history - Runs and edits of a step");
     println!("   POST /api/dreams/{{id}}/steps/{{step_id}}/process - Process open-ended reasoning");
+    println!(
+        "   GET /api/dreams/{{id}}/steps/{{step_id}}/revisions - Drafts and critiques of a reflected step"
+    );
+    println!(
+        "   POST /api/dreams/{{id}}/steps/{{step_id}}/branch - Branch a step into scored alternatives"
+    );
+    println!("   POST /api/dreams/{{id}}/steps/{{step_id}}/choose - Take a branch as the path");
     println!("   POST /api/dreams/{{id}}/steps/{{step_id}}/boolean - Process boolean reasoning");
     println!(
         "   POST /api/dreams/{{id}}/steps/{{step_id}}/choice - Process multiple choice reasoning"
@@ -31,7 +125,25 @@ async fn main() -> std::io::Result<()> {
         "   POST /api/dreams/{{id}}/steps/{{step_id}}/anchor - Anchor reasoning to blockchain"
     );
     println!("   GET /api/dreams/{{id}}/svg - Get SVG visualization");
+    println!("   GET /api/dreams/{{id}}/events - Stream dream progress (SSE)");
+    println!("   GET /api/events - Stream progress of all dreams (SSE)");
     println!("   POST /api/dreams/{{id}}/nft - Mint dream as NFT");
+    println!("   POST /api/dreams/{{id}}/mint-voucher - Sign a voucher for self-paid minting");
+    println!("   GET /api/metadata/{{id}} - NFT metadata of a dream");
+    println!("   GET /ipfs/{{cid}} - Content-addressed NFT assets");
+    println!("   POST /api/dreams/create-complete - Create, process and mint a dream in one call");
+    println!("   GET /api/pipelines - List named reasoning pipelines");
+    println!("   POST /api/dreams/continuous/start - Start a continuous dreaming session");
+    println!("   GET /api/dreams/continuous/{{session_id}} - Status of a continuous session");
+    println!("   POST /api/dreams/continuous/{{session_id}}/pause - Pause a continuous session");
+    println!("   POST /api/dreams/continuous/{{session_id}}/resume - Resume a paused session");
+    println!("   POST /api/dreams/continuous/{{session_id}}/stop - Stop a continuous session");
+    println!(
+        "   GET /api/dreams/continuous/stop?id={{id}} - Stop a session by its ID or a dream's"
+    );
+    println!("   POST /api/schedules - Schedule dreams on a cron expression");
+    println!("   GET /api/schedules - List dream schedules");
+    println!("   DELETE /api/schedules/{{id}} - Delete a dream schedule");
 
     // Start the server
     backend.serve().await
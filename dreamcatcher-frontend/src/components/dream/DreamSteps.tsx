@@ -1,6 +1,6 @@
 import React from 'react';
 import { motion } from 'framer-motion';
-import { DreamStep } from '../../services/api';
+import { DreamStep, conclusionText } from '../../services/api';
 
 interface DreamStepsProps {
   steps: DreamStep[];
@@ -187,7 +187,7 @@ const StepItem: React.FC<StepItemProps> = ({ step, index, isLast }) => {
                 borderRadius: '0.25rem',
                 fontSize: '0.875rem'
               }}>
-                {step.conclusion}
+                {conclusionText(step.conclusion)}
               </div>
             </div>
           )}
@@ -147,8 +147,8 @@ const createNewDream = async (theme: string) => {
       // Load the initial dream
       await loadDreamById(data.initial_dream_id);
       
-      // Store the control URL or dream ID for stopping later
-      setContinuousDreamId(data.initial_dream_id);
+      // Store the session ID for stopping later
+      setContinuousDreamId(data.session_id);
       
     } catch (err) {
       setError(err instanceof Error ? err.message : 'An unknown error occurred');
@@ -166,7 +166,9 @@ const createNewDream = async (theme: string) => {
       setIsLoading(true);
       
       // Use the backend's stop continuous dreaming endpoint
-      const response = await fetch(`${API_BASE_URL}/dreams/continuous/stop?id=${continuousDreamId}`);
+      const response = await fetch(`${API_BASE_URL}/dreams/continuous/${continuousDreamId}/stop`, {
+        method: 'POST'
+      });
       
       if (!response.ok) {
         throw new Error('Failed to stop continuous dreaming');
@@ -4,13 +4,133 @@ const API_BASE_URL = 'http://127.0.0.1:8080/api';
 export type StepType = 'open' | 'boolean' | 'choice' | 'numeric';
 
 // Interface for dream data
+export type Conclusion =
+  | { kind: 'open'; text: string }
+  | { kind: 'boolean'; value: boolean }
+  | { kind: 'choice'; options: string[]; selected: string }
+  | { kind: 'numeric'; min: number; max: number; value: number };
+
+// Human-readable form of a typed conclusion
+export const conclusionText = (conclusion: Conclusion): string => {
+  switch (conclusion.kind) {
+    case 'open':
+      return conclusion.text;
+    case 'boolean':
+      return conclusion.value ? 'TRUE' : 'FALSE';
+    case 'choice':
+      return conclusion.selected;
+    case 'numeric':
+      return `${conclusion.value} (${conclusion.min}-${conclusion.max})`;
+  }
+};
+
 export interface DreamStep {
   step_id: number;
   desc: string;
   reasoning: string;
-  conclusion?: string;
+  conclusion?: Conclusion;
+  // First sentence of an open step's conclusion
+  summary?: string;
   anchored?: boolean;
   tx_hash?: string;
+  block_number?: number;
+  anchor_digest?: string;
+  context_steps?: number[];
+  confidence?: number;
+  votes?: StepVotes;
+  revisions?: StepRevision[];
+  // Set on branches: the step they fork from, their 1-10 score and whether they're the path taken
+  parent?: number;
+  score?: number;
+  chosen?: boolean;
+  // The steps this one builds on, when it doesn't simply build on the steps before it
+  depends_on?: number[];
+  // How the step was last run, and its runs and edits
+  spec?: StepSpec;
+  history?: StepHistoryEntry[];
+  // The step changed after it was anchored
+  stale?: boolean;
+  // Every model call of the step's latest run
+  calls?: ModelCall[];
+}
+
+// Exactly what the model was sent for a step, context included, and what it replied
+export interface ModelCall {
+  prompt: string;
+  response: string;
+  model?: string;
+  latency_ms: number;
+  usage?: {
+    prompt_tokens: number;
+    completion_tokens: number;
+    total_tokens: number;
+  };
+}
+
+// A run of a step, or an edit of what it asks
+export interface StepHistoryEntry {
+  action: 'processed' | 'edited';
+  description: string;
+  prompt?: string;
+  model?: string;
+  response?: string;
+  conclusion?: Conclusion;
+  created_at: string;
+}
+
+export interface StepEdit {
+  description?: string;
+  prompt?: string;
+  depends_on?: number[];
+}
+
+// A step with the branches that fork from it
+export interface StepNode {
+  step_id: number;
+  desc: string;
+  score?: number;
+  chosen?: boolean;
+  children: StepNode[];
+}
+
+export interface BranchResult {
+  step_id: number;
+  chosen: number | null;
+  branches: DreamStep[];
+}
+
+// A draft of a reflected step's reasoning, or the critique of the draft before it
+export interface StepRevision {
+  round: number;
+  kind: 'draft' | 'critique';
+  text: string;
+  created_at: string;
+}
+
+// How the sampled answers of a voted step split; `agreement` is the winning share, 0-1
+export interface StepVotes {
+  samples: number;
+  distribution: Record<string, number>;
+  agreement: number;
+}
+
+export interface DreamNft {
+  ipfs_cid: string;
+  metadata_cid: string;
+  token_uri: string;
+  token_id: string;
+  owner: string;
+  transaction_hash: string;
+  // An anchored step changed or was deleted after minting
+  stale: boolean;
+}
+
+export interface MintVoucher {
+  token_uri: string;
+  wallet: string;
+  signature: string;
+  signer: string;
+  contract: string | null;
 }
 
 export interface Dream {
@@ -18,6 +138,9 @@ export interface Dream {
   title?: string;
   theme?: string;
   steps: DreamStep[];
+  plan?: StepSpec[];
+  tree?: StepNode[];
+  nft?: DreamNft;
 }
 
 export interface CompleteCreateDreamResponse {
@@ -25,19 +148,119 @@ export interface CompleteCreateDreamResponse {
   title: string;
   theme: string;
   steps: DreamStep[];
+  plan: StepSpec[] | null;
+  tree: StepNode[] | null;
   svg_url: string;
-  nft?: {
-    ipfs_cid: string;
-    transaction_hash: string;
-  };
+  nft?: DreamNft;
+}
+
+export type DreamEvent = { dream_id: string } & (
+  | { type: 'dream-created'; title?: string; theme?: string }
+  | { type: 'steps-planned'; steps: StepSpec[] }
+  | { type: 'step-created'; step_id: number; desc: string; parent?: number }
+  | { type: 'token-delta'; step_id: number; delta: string }
+  | { type: 'step-revised'; step_id: number; revision: StepRevision }
+  | { type: 'step-concluded'; step_id: number; conclusion?: Conclusion }
+  | { type: 'step-edited'; step_id: number; desc: string }
+  | { type: 'step-deleted'; step_id: number }
+  | { type: 'branch-chosen'; parent: number; step_id: number }
+  | { type: 'anchored'; step_id: number; tx_hash: string; block_number?: number; digest: string }
+  | { type: 'svg-ready'; svg_url: string }
+  | { type: 'nft-minted'; nft: DreamNft }
+);
+
+const DREAM_EVENT_TYPES = [
+  'dream-created',
+  'steps-planned',
+  'step-created',
+  'token-delta',
+  'step-revised',
+  'step-concluded',
+  'step-edited',
+  'step-deleted',
+  'branch-chosen',
+  'anchored',
+  'svg-ready',
+  'nft-minted',
+];
+
+// A reasoning step to run; `prompt` may use {theme} and {previous} placeholders. Open steps may
+// run up to 3 critique-and-revise rounds (`reflect`), and typed steps may vote over up to 9
+// `samples`. `depends_on` lists the positions of the steps it builds on; steps that don't
+// depend on each other run at the same time.
+export type StepSpec = { description: string; prompt?: string; depends_on?: number[] } & (
+  | { kind: 'open'; reflect?: number }
+  | { kind: 'boolean'; samples?: number }
+  | { kind: 'choice'; options: string[]; samples?: number }
+  | { kind: 'numeric'; min: number; max: number; samples?: number }
+);
+
+// How a dream picks its steps: plain descriptions, full step specs, a server-side pipeline or
+// a plan of `plan_steps` steps written by the agent (the default)
+export type StepSelection =
+  | string[]
+  | { steps: StepSpec[] }
+  | { pipeline: string }
+  | { plan_steps: number };
+
+const stepFields = (selection?: StepSelection) =>
+  Array.isArray(selection) ? { reasoning_steps: selection } : selection ?? {};
+
+export interface ContinuousDreamOptions {
+  intervalSecs?: number;
+  maxIterations?: number;
+  evolveTheme?: boolean;
 }
 
 export interface ContinuousDreamResponse {
   status: string;
+  session_id: string;
   initial_dream_id: string;
+  interval_secs: number;
+  max_iterations: number | null;
+  status_url: string;
   control_url: string;
 }
 
+export interface DreamScheduleRequest {
+  cron: string;
+  theme: string;
+  reasoning_steps?: string[];
+  steps?: StepSpec[];
+  pipeline?: string;
+  plan_steps?: number;
+  auto_mint?: boolean;
+}
+
+export interface DreamSchedule {
+  id: string;
+  cron: string;
+  theme: string;
+  reasoning_steps?: string[];
+  steps?: StepSpec[];
+  pipeline?: string;
+  plan_steps?: number;
+  auto_mint: boolean;
+  created_at: string;
+  last_run_at: string | null;
+  last_dream_id: string | null;
+  last_error: string | null;
+  next_run_at: string | null;
+}
+
+export type ContinuousSessionState = 'running' | 'paused' | 'stopped' | 'finished';
+
+export interface ContinuousSession {
+  session_id: string;
+  state: ContinuousSessionState;
+  theme: string;
+  interval_secs: number;
+  max_iterations: number | null;
+  dream_ids: string[];
+  next_run_at: string | null;
+  last_error: string | null;
+}
+
 // API service for dream operations
 export const dreamService = {
   // Create a new dream
@@ -57,13 +280,13 @@ export const dreamService = {
   },
   
   // Create a complete dream in one API call (recommended approach)
-  createCompleteDream: async (theme: string, customSteps?: string[], autoMint: boolean = true): Promise<CompleteCreateDreamResponse> => {
+  createCompleteDream: async (theme: string, customSteps?: StepSelection, autoMint: boolean = true): Promise<CompleteCreateDreamResponse> => {
     const response = await fetch(`${API_BASE_URL}/dreams/create-complete`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
         theme,
-        reasoning_steps: customSteps,
+        ...stepFields(customSteps),
         auto_mint: autoMint
       })
     });
@@ -76,13 +299,16 @@ export const dreamService = {
   },
   
   // Start continuous dreaming
-  startContinuousDreaming: async (theme: string, customSteps?: string[]): Promise<ContinuousDreamResponse> => {
+  startContinuousDreaming: async (theme: string, customSteps?: StepSelection, options: ContinuousDreamOptions = {}): Promise<ContinuousDreamResponse> => {
     const response = await fetch(`${API_BASE_URL}/dreams/continuous/start`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
         theme,
-        reasoning_steps: customSteps
+        ...stepFields(customSteps),
+        interval_secs: options.intervalSecs,
+        max_iterations: options.maxIterations,
+        evolve_theme: options.evolveTheme
       })
     });
     
@@ -93,17 +319,83 @@ export const dreamService = {
     return response.json();
   },
   
+  // Get a continuous dreaming session's progress
+  getContinuousSession: async (sessionId: string): Promise<ContinuousSession> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/continuous/${sessionId}`);
+    
+    if (!response.ok) {
+      throw new Error('Failed to fetch continuous dreaming session');
+    }
+    
+    return response.json();
+  },
+  
+  // Pause, resume or stop continuous dreaming
+  controlContinuousSession: async (sessionId: string, action: 'pause' | 'resume' | 'stop'): Promise<ContinuousSession> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/continuous/${sessionId}/${action}`, {
+      method: 'POST'
+    });
+    
+    if (!response.ok) {
+      throw new Error(`Failed to ${action} continuous dreaming`);
+    }
+    
+    return response.json();
+  },
+  
   // Stop continuous dreaming
-  stopContinuousDreaming: async (dreamId: string): Promise<{status: string, dream_id: string}> => {
-    const response = await fetch(`${API_BASE_URL}/dreams/continuous/stop?id=${dreamId}`);
+  stopContinuousDreaming: async (sessionId: string): Promise<ContinuousSession> =>
+    dreamService.controlContinuousSession(sessionId, 'stop'),
+  
+  // Named step pipelines the server offers
+  getPipelines: async (): Promise<Record<string, StepSpec[]>> => {
+    const response = await fetch(`${API_BASE_URL}/pipelines`);
+    
+    if (!response.ok) {
+      throw new Error('Failed to fetch pipelines');
+    }
+    
+    return response.json();
+  },
+  
+  // Schedule dreams on a cron expression (UTC)
+  createSchedule: async (schedule: DreamScheduleRequest): Promise<DreamSchedule> => {
+    const response = await fetch(`${API_BASE_URL}/schedules`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(schedule)
+    });
+    
+    if (!response.ok) {
+      const data = await response.json().catch(() => ({}));
+      throw new Error(data.error || 'Failed to create schedule');
+    }
+    
+    return response.json();
+  },
+  
+  // List dream schedules
+  getSchedules: async (): Promise<DreamSchedule[]> => {
+    const response = await fetch(`${API_BASE_URL}/schedules`);
     
     if (!response.ok) {
-      throw new Error('Failed to stop continuous dreaming');
+      throw new Error('Failed to fetch schedules');
     }
     
     return response.json();
   },
   
+  // Delete a dream schedule
+  deleteSchedule: async (scheduleId: string): Promise<void> => {
+    const response = await fetch(`${API_BASE_URL}/schedules/${scheduleId}`, {
+      method: 'DELETE'
+    });
+    
+    if (!response.ok) {
+      throw new Error('Failed to delete schedule');
+    }
+  },
+  
   // Get dream details
   getDream: async (id: string): Promise<Dream> => {
     const response = await fetch(`${API_BASE_URL}/dreams/${id}`);
@@ -116,13 +408,14 @@ export const dreamService = {
   },
   
   // Add a reasoning step
-  addStep: async (dreamId: string, description: string): Promise<number> => {
+  addStep: async (dreamId: string, description: string, dependsOn?: number[]): Promise<number> => {
     const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
         description, 
-        prompt: description 
+        prompt: description,
+        depends_on: dependsOn
       })
     });
     
@@ -135,15 +428,15 @@ export const dreamService = {
   },
   
   // Process a step with open-ended reasoning
-  processStep: async (dreamId: string, stepId: number, prompt: string): Promise<DreamStep> => {
+  processStep: async (dreamId: string, stepId: number, prompt: string, reflect?: number): Promise<DreamStep> => {
     console.log(`[API] Processing step ${stepId} for dream ${dreamId}`);
     
     const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/process`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
-        description: prompt,
-        prompt: prompt 
+        prompt: prompt,
+        reflect: reflect
       })
     });
     
@@ -162,14 +455,101 @@ export const dreamService = {
   },
 
   
+  // Fork a concluded step into 2-5 scored alternative continuations
+  branchStep: async (dreamId: string, stepId: number, branches?: number, focus?: string): Promise<BranchResult> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/branch`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ branches, focus })
+    });
+    
+    if (!response.ok) {
+      throw new Error(`Failed to branch step: ${await response.text()}`);
+    }
+    
+    return response.json();
+  },
+  
+  // Take a branch as the path instead of the best-scored one
+  chooseBranch: async (dreamId: string, stepId: number): Promise<void> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/choose`, {
+      method: 'POST'
+    });
+    
+    if (!response.ok) {
+      throw new Error(`Failed to choose branch: ${await response.text()}`);
+    }
+  },
+  
+  // Change a step's description, prompt or dependencies
+  editStep: async (dreamId: string, stepId: number, edit: StepEdit): Promise<DreamStep> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}`, {
+      method: 'PATCH',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(edit)
+    });
+    
+    if (!response.ok) {
+      throw new Error(`Failed to edit step: ${await response.text()}`);
+    }
+    
+    return response.json();
+  },
+  
+  deleteStep: async (dreamId: string, stepId: number): Promise<void> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}`, {
+      method: 'DELETE'
+    });
+    
+    if (!response.ok) {
+      throw new Error(`Failed to delete step: ${await response.text()}`);
+    }
+  },
+  
+  // Run a step again the way it was last run
+  rerunStep: async (dreamId: string, stepId: number): Promise<DreamStep> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/rerun`, {
+      method: 'POST'
+    });
+    
+    if (!response.ok) {
+      throw new Error(`Failed to rerun step: ${await response.text()}`);
+    }
+    
+    return response.json();
+  },
+  
+  getStepHistory: async (dreamId: string, stepId: number): Promise<StepHistoryEntry[]> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/history`);
+    
+    if (!response.ok) {
+      throw new Error(`Failed to fetch step history: ${await response.text()}`);
+    }
+    
+    const data = await response.json();
+    return data.history;
+  },
+  
+  // Drafts and critiques of a step processed with `reflect`
+  getStepRevisions: async (dreamId: string, stepId: number): Promise<StepRevision[]> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/revisions`);
+    
+    if (!response.ok) {
+      throw new Error(`Failed to fetch step revisions: ${await response.text()}`);
+    }
+    
+    const data = await response.json();
+    return data.revisions;
+  },
+  
   // Process a step with boolean reasoning
-  processBooleanStep: async (dreamId: string, stepId: number, prompt: string): Promise<boolean> => {
+  processBooleanStep: async (dreamId: string, stepId: number, prompt: string, samples?: number): Promise<boolean> => {
     const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/boolean`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
-        description: prompt,
-        prompt: prompt 
+        prompt: prompt,
+        samples: samples
       })
     });
     
@@ -182,14 +562,14 @@ export const dreamService = {
   },
   
   // Process a step with multiple choice reasoning
-  processChoiceStep: async (dreamId: string, stepId: number, prompt: string, options: string[]): Promise<string> => {
+  processChoiceStep: async (dreamId: string, stepId: number, prompt: string, options: string[], samples?: number): Promise<string> => {
     const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/choice`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
-        description: prompt,
         prompt: prompt,
-        options: options
+        options: options,
+        samples: samples
       })
     });
     
@@ -202,15 +582,15 @@ export const dreamService = {
   },
 
   // Process a step with numeric reasoning
-  processNumericStep: async (dreamId: string, stepId: number, prompt: string, min: number, max: number): Promise<number> => {
+  processNumericStep: async (dreamId: string, stepId: number, prompt: string, min: number, max: number, samples?: number): Promise<number> => {
     const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/steps/${stepId}/numeric`, {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({ 
-        description: prompt,
         prompt: prompt,
         min: min,
-        max: max
+        max: max,
+        samples: samples
       })
     });
     
@@ -248,7 +628,7 @@ export const dreamService = {
   },
   
   // Mint dream as NFT
-  mintDreamNft: async (dreamId: string): Promise<{ipfs_cid: string, transaction_hash: string}> => {
+  mintDreamNft: async (dreamId: string): Promise<DreamNft> => {
     const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/nft`, {
       method: 'POST'
     });
@@ -260,6 +640,39 @@ export const dreamService = {
     return response.json();
   },
   
+  // Get an owner-signed voucher for DreamNFT.mintWithSignature from the user's wallet
+  getMintVoucher: async (dreamId: string, wallet: string): Promise<MintVoucher> => {
+    const response = await fetch(`${API_BASE_URL}/dreams/${dreamId}/mint-voucher`, {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json',
+      },
+      body: JSON.stringify({ wallet })
+    });
+    
+    if (!response.ok) {
+      throw new Error('Failed to get mint voucher');
+    }
+    
+    return response.json();
+  },
+  
+  // Follow a dream's progress live (or every dream's, without an ID); returns an unsubscribe function
+  subscribeToEvents: (onEvent: (event: DreamEvent) => void, dreamId?: string): (() => void) => {
+    const url = dreamId
+      ? `${API_BASE_URL}/dreams/${dreamId}/events`
+      : `${API_BASE_URL}/events`;
+    const source = new EventSource(url);
+    
+    DREAM_EVENT_TYPES.forEach(type => {
+      source.addEventListener(type, message => {
+        onEvent(JSON.parse((message as MessageEvent).data));
+      });
+    });
+    
+    return () => source.close();
+  },
+  
   // Helper function to properly process all steps for a dream in sequence
   processAllSteps: async (dreamId: string): Promise<Dream> => {
     try {
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+contract DreamAnchor {
+    // Event emitted for every anchored reasoning step digest
+    event DreamAnchored(bytes32 indexed digest, address indexed sender, uint256 timestamp);
+
+    /**
+     * @dev Records the digest of a reasoning step
+     * @param digest keccak256(abi.encode(step)) of the step encoded as one struct value:
+     *   struct Step { string description; string[] prompts; string reasoning; string conclusion; }
+     * where `prompts` holds what each of the step's model calls sent, in order. Steps run
+     * before model calls were recorded use
+     *   struct Step { string description; string reasoning; string conclusion; }
+     * Encoding the struct as a single value puts a 0x20 offset word in front of
+     * abi.encode(description, prompts, reasoning, conclusion). `conclusion` is the open-ended
+     * text, "TRUE" or "FALSE", the chosen option or the number in decimal, or "" if none.
+     */
+    function anchor(bytes32 digest) public {
+        emit DreamAnchored(digest, msg.sender, block.timestamp);
+    }
+}
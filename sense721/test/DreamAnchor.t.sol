@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {Test} from "forge-std/Test.sol";
+import {DreamAnchor} from "../src/DreamAnchor.sol";
+
+contract DreamAnchorTest is Test {
+    DreamAnchor public anchor;
+
+    event DreamAnchored(bytes32 indexed digest, address indexed sender, uint256 timestamp);
+
+    function setUp() public {
+        anchor = new DreamAnchor();
+    }
+
+    function testAnchorEmitsDigest() public {
+        bytes32 digest = keccak256(abi.encode("Current state analysis", "Reasoning", "Conclusion"));
+
+        vm.expectEmit(true, true, false, true);
+        emit DreamAnchored(digest, address(this), block.timestamp);
+
+        anchor.anchor(digest);
+    }
+}